@@ -1,11 +1,15 @@
 #![cfg(not(target_family = "wasm"))]
 
+use crate::bit_board::columns;
 use crate::board::*;
 use crate::move_engine::*;
 use crate::score;
+use crate::skill::*;
+use crate::timer::Clock;
 
 use clap::*;
 use std::io;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -19,38 +23,273 @@ struct Cli {
 enum Commands {
     /// Compute the best move
     Bestmove(EngineArgs),
+    /// Score every legal move, best first
+    Analyze(EngineArgs),
+    /// Prove the value of a position
+    Solve(SolveArgs),
     /// Play a game against the cpu in the terminal
-    Play,
+    Play(PlayArgs),
 }
 
 #[derive(Args)]
 struct EngineArgs {
-    /// Max time spent calculating
-    #[arg(short, long, default_value_t = 3)]
-    time: u64,
+    /// Max time spent calculating [default: 3, none with --depth or --nodes]
+    #[arg(short, long)]
+    time: Option<u64>,
 
-    /// Transpositin table entrys
-    #[arg(short, long, default_value_t = 100_000)]
-    size: usize,
+    /// Time for the move (e.g. 250ms, 2s), overrides --time
+    #[arg(long, value_parser = parse_duration)]
+    movetime: Option<Duration>,
+
+    /// Time left on the clock (e.g. 60s), the engine plans the move time
+    #[arg(long, value_parser = parse_duration)]
+    remaining: Option<Duration>,
+
+    /// Time added to the clock after every move
+    #[arg(long, default_value = "0", value_parser = parse_duration, requires = "remaining")]
+    increment: Duration,
+
+    /// Moves until the next time control
+    #[arg(long, requires = "remaining")]
+    moves_to_go: Option<u32>,
+
+    /// Transposition table memory (e.g. 256MB, 1GB)
+    #[arg(long, default_value = "16MB", value_parser = parse_memory)]
+    hash: usize,
+
+    /// Search threads
+    #[arg(long, default_value_t = 1)]
+    threads: usize,
+
+    /// Max search depth, the result is the same on every run
+    #[arg(long)]
+    depth: Option<u8>,
+
+    /// Max searched nodes, the result is the same on every run
+    #[arg(long)]
+    nodes: Option<u64>,
+
+    #[command(flatten)]
+    skill: SkillArgs,
+
+    /// Search without time limit until Enter is pressed
+    #[arg(long)]
+    infinite: bool,
 
     /// Game position
     #[arg(short, long, default_value_t = String::from(""))]
     position: String,
 }
 
+#[derive(Args)]
+struct SkillArgs {
+    /// Playing strength from 0 to 10 (full strength)
+    #[arg(long, default_value_t = MAX_SKILL, value_parser = clap::value_parser!(u8).range(0..=MAX_SKILL as i64))]
+    skill: u8,
+
+    /// Seed of the random choices of a weaker skill, from the clock if not given
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+impl SkillArgs {
+    fn skill(&self) -> Skill {
+        let seed = self.seed.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos() as u64)
+        });
+        Skill::new(self.skill, seed)
+    }
+}
+
+#[derive(Args)]
+struct PlayArgs {
+    #[command(flatten)]
+    skill: SkillArgs,
+
+    /// Keep searching while the human thinks, only at full skill
+    #[arg(long)]
+    ponder: bool,
+}
+
+impl PlayArgs {
+    /// a weaker skill makes the engine deterministic, it would not ponder
+    fn check(&self) -> Result<(), String> {
+        if self.ponder && self.skill.skill < MAX_SKILL {
+            return Err(format!("--ponder needs --skill {}", MAX_SKILL));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+struct SolveArgs {
+    /// Max time spent solving, unlimited if not given
+    #[arg(short, long)]
+    time: Option<u64>,
+
+    /// Only tell if the side to move wins, draws or loses
+    #[arg(short, long)]
+    weak: bool,
+
+    /// Transposition table memory (e.g. 256MB, 1GB)
+    #[arg(long, default_value = "16MB", value_parser = parse_memory)]
+    hash: usize,
+
+    /// Game position
+    #[arg(short, long, default_value_t = String::from(""))]
+    position: String,
+}
+
+const DEFAULT_HASH: usize = 16 << 20;
+const DEFAULT_TIME: u64 = 3;
+
+/// parse a memory size like `256MB`, a plain number is in MB
+fn parse_memory(s: &str) -> Result<usize, String> {
+    let s = s.trim().to_uppercase();
+    let (digits, shift) = if let Some(n) = s.strip_suffix("GB") {
+        (n, 30)
+    } else if let Some(n) = s.strip_suffix("MB") {
+        (n, 20)
+    } else if let Some(n) = s.strip_suffix("KB") {
+        (n, 10)
+    } else if let Some(n) = s.strip_suffix('B') {
+        (n, 0)
+    } else {
+        (s.as_str(), 20)
+    };
+    let n: usize = digits
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid memory size `{}`", s))?;
+    n.checked_shl(shift)
+        .filter(|bytes| bytes >> shift == n)
+        .ok_or_else(|| format!("memory size `{}` too big", s))
+}
+
+/// parse a duration like `250ms` or `2s`, a plain number is in milliseconds
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim().to_lowercase();
+    let (digits, millis) = if let Some(n) = s.strip_suffix("ms") {
+        (n, 1)
+    } else if let Some(n) = s.strip_suffix('s') {
+        (n, 1000)
+    } else {
+        (s.as_str(), 1)
+    };
+    let n: u64 = digits
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid duration `{}`", s))?;
+    n.checked_mul(millis)
+        .map(Duration::from_millis)
+        .ok_or_else(|| format!("duration `{}` too long", s))
+}
+
+/// engine set up by the search options, printing its progress
+fn engine(args: &EngineArgs) -> Engine {
+    let mut e = Engine::new(args.time.unwrap_or(DEFAULT_TIME), args.hash);
+    e.set_max_depth(args.depth);
+    e.set_max_nodes(args.nodes);
+    e.set_skill(args.skill.skill());
+    let limited = args.depth.is_some() || args.nodes.is_some();
+    if limited && args.time.is_none() && args.movetime.is_none() {
+        e.set_infinite();
+    }
+    if let Some(movetime) = args.movetime {
+        e.set_movetime(movetime);
+    }
+    if let Some(remaining) = args.remaining {
+        e.set_clock(Clock {
+            remaining,
+            increment: args.increment,
+            moves_to_go: args.moves_to_go,
+        });
+    }
+    e.set_threads(args.threads);
+    println!("TABLE: {} entries", e.table_entries());
+    e.set_progress(|m, info| {
+        println!(
+            "info depth {} score {} nodes {} nps {} time {} pv {}",
+            info.depth,
+            m.score(),
+            info.nodes,
+            info.nps(),
+            info.elapsed.as_millis(),
+            m.pv()
+        )
+    });
+    if args.infinite {
+        e.set_infinite();
+        let handle = e.stop_handle();
+        std::thread::spawn(move || {
+            let mut buf = String::new();
+            io::stdin().read_line(&mut buf).unwrap();
+            handle.stop();
+        });
+    }
+    e
+}
+
 pub fn app() {
     let cli = Cli::parse();
 
     match &cli.command {
         Commands::Bestmove(args) => {
-            let mut e = Engine::new(args.time, args.size);
+            let mut e = engine(args);
             let b = Board::from_position(&args.position);
             let m = e.iterative_depening(&b);
             println!("{:?}", m);
+            println!("PV: {}", m.pv());
+            println!("INFO: {}", e.info());
+        }
+        Commands::Analyze(args) => {
+            let mut e = engine(args);
+            let b = Board::from_position(&args.position);
+            for m in e.analyze(&b) {
+                println!(
+                    "move {} score {} depth {} {} pv {}",
+                    m.col(),
+                    score::ScoreMethods::to_string(&m.score()),
+                    m.depth(),
+                    if m.is_proven() { "proven" } else { "estimate" },
+                    m.pv()
+                );
+            }
+            println!("INFO: {}", e.info());
+        }
+        Commands::Solve(args) => {
+            let mut e = Engine::new(0, args.hash);
+            let b = Board::from_position(&args.position);
+            if args.weak {
+                match e.weak_solve(&b, args.time) {
+                    Some(outcome) => println!("{:?} for {:?}", outcome, b.player()),
+                    None => println!("time is up"),
+                }
+                return;
+            }
+            let s = e.solve(&b, args.time);
+            println!("{:?}", s);
+            println!("SCORE: {}", score::ScoreMethods::to_string(&s.score));
+            if let Some(d) = s.distance {
+                println!("DISTANCE: {} moves", d);
+            }
+            println!("PROVEN: {}", s.proven);
+            if !s.proven {
+                println!("BOUNDS: {} {}", s.lower, s.upper);
+            }
+            println!("PV: {}", s.pv);
         }
-        Commands::Play => {
+        Commands::Play(args) => {
+            if let Err(e) = args.check() {
+                Cli::command()
+                    .error(error::ErrorKind::ArgumentConflict, e)
+                    .exit();
+            }
             let mut board = Board::new();
-            let mut e = Engine::new(3, 100_000);
+            let mut e = Engine::new(DEFAULT_TIME, DEFAULT_HASH);
+            e.set_skill(args.skill.skill());
 
             println!("p1 for first player, p2 for second");
             let mut buf = String::new();
@@ -68,12 +307,20 @@ pub fn app() {
                     io::stdin().read_line(&mut buf).unwrap();
                     let col: u8 = buf.trim().parse().unwrap();
 
+                    let safe = board.safe_moves();
+                    if safe != 0 && !columns(safe).any(|c| c == col) {
+                        println!("WARNING: {} lets the opponent win", col);
+                    }
                     board.make_move(col);
                 } else if board.player() != human && board.gamestate() == GameState::Open {
                     let m = e.iterative_depening(&board);
                     board.make_move(m.col());
                     println!("{:?}", m);
-                    println!("SCORE: {}", score::ScoreMethods::to_string(&m.score()))
+                    println!("SCORE: {}", score::ScoreMethods::to_string(&m.score()));
+                    println!("PV: {}", m.pv());
+                    if args.ponder {
+                        e.ponder(&board, m.pv().moves().get(1).copied());
+                    }
                 }
 
                 board.bitboard().print();
@@ -84,3 +331,56 @@ pub fn app() {
     /*
      */
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory() {
+        assert_eq!(parse_memory("256MB"), Ok(256 << 20));
+        assert_eq!(parse_memory("1gb"), Ok(1 << 30));
+        assert_eq!(parse_memory("512KB"), Ok(512 << 10));
+        assert_eq!(parse_memory("64"), Ok(64 << 20));
+        assert!(parse_memory("lots").is_err());
+    }
+
+    #[test]
+    fn depth() {
+        let bestmove = |depth: &str| {
+            let cli = Cli::try_parse_from(["", "bestmove", "--depth", depth, "-p", "33322224"]);
+            let Commands::Bestmove(args) = cli.unwrap().command else {
+                unreachable!()
+            };
+            let b = Board::from_position(&args.position);
+            engine(&args).iterative_depening(&b)
+        };
+        // the best move changes between depths of this position
+        let (a, b) = (bestmove("10"), bestmove("10"));
+        assert_eq!(a.depth(), 10);
+        assert_eq!((a.col(), a.score(), *a.pv()), (b.col(), b.score(), *b.pv()));
+        assert!(bestmove("255").depth() > 0);
+    }
+
+    #[test]
+    fn ponder() {
+        let play = |args: &[&str]| {
+            let cli = Cli::try_parse_from([&["", "play"], args].concat());
+            let Commands::Play(args) = cli.unwrap().command else {
+                unreachable!()
+            };
+            args.check()
+        };
+        assert!(play(&["--ponder"]).is_ok());
+        assert!(play(&["--skill", "3"]).is_ok());
+        assert!(play(&["--skill", "3", "--ponder"]).is_err());
+    }
+
+    #[test]
+    fn duration() {
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("2s"), Ok(Duration::from_secs(2)));
+        assert_eq!(parse_duration("100"), Ok(Duration::from_millis(100)));
+        assert!(parse_duration("soon").is_err());
+    }
+}
@@ -3,10 +3,13 @@
 
 use dioxus::prelude::*;
 
+use crate::bit_board::columns;
 use crate::board::*;
 use crate::move_engine::*;
 use crate::r#move::Move;
 use crate::score::*;
+use crate::skill::*;
+use std::time::Duration;
 
 #[derive(Debug, Clone, Copy)]
 enum CellType {
@@ -18,22 +21,15 @@ enum CellType {
 fn board_to_arr(board: &Board) -> [[CellType; COL as usize]; ROW as usize] {
     let mut arr = [[CellType::Empty; COL as usize]; ROW as usize];
     let bb = board.bitboard();
-    let spacearr = bb.get_space_array();
 
     for row in 0..ROW {
         for col in 0..COL {
             let rrow = ROW - row - 1;
-            if ROW - spacearr[col as usize] <= row {
-                arr[rrow as usize][col as usize] = CellType::Empty;
-            } else {
-                let offset: u64 = row * COL + (col as u64);
-                let bit = (bb.board().board() & (0b1 << offset)) >> (offset);
-                if bit == 0 {
-                    arr[rrow as usize][col as usize] = CellType::P1;
-                } else {
-                    arr[rrow as usize][col as usize] = CellType::P2;
-                }
-            }
+            arr[rrow as usize][col as usize] = match bb.board().cell(col, row) {
+                None => CellType::Empty,
+                Some(Player::P1) => CellType::P1,
+                Some(Player::P2) => CellType::P2,
+            };
         }
     }
     arr
@@ -44,6 +40,11 @@ fn Board(cx: Scope) -> Element {
     let arr = board_to_arr(&board.read());
     let spaces = board.read().bitboard().get_space_array();
     let state = board.read().gamestate();
+    let safe = board.read().safe_moves();
+    let mut losing = [safe != 0; 7];
+    for col in columns(safe) {
+        losing[col as usize] = false;
+    }
     cx.render(rsx! {
         div {
             "style": "max-width: 50em; margin: auto;",
@@ -57,6 +58,8 @@ fn Board(cx: Scope) -> Element {
                                 td { 
                                     button {
                                         disabled: (spaces[i as usize] <= 0) || (board.read().gamestate() != GameState::Open),
+                                        "style": if losing[i as usize] { "color: red;" } else { "" },
+                                        title: if losing[i as usize] { "lets the opponent win" } else { "" },
                                         onclick: move |_| {
                                             board.write().make_move(i)
                                         },
@@ -153,22 +156,32 @@ impl PlayerType {
     }
 }
 
-const DEFAULT_SECS: u64 = 3;
-const DEFAULT_TABLE_SIZE: usize = 100_000;
+const DEFAULT_MOVETIME_MS: u64 = 3000;
+const DEFAULT_TABLE_MB: usize = 16;
+
+fn new_engine() -> Engine {
+    let mut e = Engine::new(0, DEFAULT_TABLE_MB << 20);
+    e.set_movetime(Duration::from_millis(DEFAULT_MOVETIME_MS));
+    e
+}
 
 fn App(cx: Scope) -> Element {
     use_shared_state_provider(cx, || Board::new());
     let board = use_shared_state::<Board>(cx).unwrap();
 
-    let e1 = use_ref(cx, || Engine::new(DEFAULT_SECS, DEFAULT_TABLE_SIZE));
-    let e2 = use_ref(cx, || Engine::new(DEFAULT_SECS, DEFAULT_TABLE_SIZE));
+    let e1 = use_ref(cx, new_engine);
+    let e2 = use_ref(cx, new_engine);
     let p1 = use_state(cx, || PlayerType::Human);
     let p2 = use_state(cx, || PlayerType::Human);
 
-    let p1t = use_state(cx, || DEFAULT_SECS);
-    let p1m = use_state(cx, || DEFAULT_TABLE_SIZE);
-    let p2t = use_state(cx, || DEFAULT_SECS);
-    let p2m = use_state(cx, || DEFAULT_TABLE_SIZE);
+    let p1t = use_state(cx, || DEFAULT_MOVETIME_MS);
+    let p1m = use_state(cx, || DEFAULT_TABLE_MB);
+    let p2t = use_state(cx, || DEFAULT_MOVETIME_MS);
+    let p2m = use_state(cx, || DEFAULT_TABLE_MB);
+    let p1s = use_state(cx, || MAX_SKILL);
+    let p1r = use_state(cx, || 0u64);
+    let p2s = use_state(cx, || MAX_SKILL);
+    let p2r = use_state(cx, || 0u64);
 
     let m1 = use_state(cx, || Move::new(0, Player::P1, EQUAL, 0));
     let m2 = use_state(cx, || Move::new(0, Player::P1, EQUAL, 0));
@@ -190,10 +203,13 @@ fn App(cx: Scope) -> Element {
                     rsx! {
                         div {
                             p {"{m1}"},
+                            p {"PV: {m1.pv()}"},
+                            p {"Search: {e1.read().info()}"},
                             input {
                                 r#type: "number",
                                 id: "p1t",
                                 "min": 1,
+                                "step": 100,
                                 value: "{p1t}",
                                 oninput: move |evt| {
                                     if !evt.value.is_empty() {
@@ -202,36 +218,72 @@ fn App(cx: Scope) -> Element {
                                             Ok(num) => num
                                         };
                                         p1t.set(n);
-                                        e1.with_mut(|e1| e1.set_time(n))
+                                        e1.with_mut(|e1| e1.set_movetime(Duration::from_millis(n)))
                                     }
                                 }
                             }
                             label {
                                 "for": "p1t",
-                                "Time (s)"
+                                "Time (ms)"
                             }
                             br {}
                             input {
                                 r#type: "number",
                                 id: "p1m",
-                                "min": 1_000,
-                                "max": 1_000_000,
-                                "step": 50_000,
+                                "min": 1,
+                                "max": 1024,
+                                "step": 16,
                                 value: "{p1m}",
                                 oninput: move |evt| {
                                     if !evt.value.is_empty() {
                                         let n: usize = match evt.value.parse() {
-                                            Err(_) => DEFAULT_TABLE_SIZE,
+                                            Err(_) => DEFAULT_TABLE_MB,
                                             Ok(num) => num
                                         };
                                         p1m.set(n);
-                                        e1.with_mut(|e1| e1.set_table(n))
+                                        e1.with_mut(|e1| e1.set_table(n << 20))
                                     }
                                 }
                             }
                             label {
                                 "for": "p1m",
-                                "Table size (entrys)"
+                                "Table size (MB, {e1.read().table_entries()} entries)"
+                            }
+                            br {}
+                            input {
+                                r#type: "number",
+                                id: "p1s",
+                                "min": 0,
+                                "max": 10,
+                                value: "{p1s}",
+                                oninput: move |evt| {
+                                    if let Ok(n) = evt.value.parse::<u8>() {
+                                        let n = n.min(MAX_SKILL);
+                                        p1s.set(n);
+                                        e1.with_mut(|e1| e1.set_skill(Skill::new(n, *p1r.get())))
+                                    }
+                                }
+                            }
+                            label {
+                                "for": "p1s",
+                                "Skill (0-{MAX_SKILL})"
+                            }
+                            br {}
+                            input {
+                                r#type: "number",
+                                id: "p1r",
+                                "min": 0,
+                                value: "{p1r}",
+                                oninput: move |evt| {
+                                    if let Ok(n) = evt.value.parse::<u64>() {
+                                        p1r.set(n);
+                                        e1.with_mut(|e1| e1.set_skill(Skill::new(*p1s.get(), n)))
+                                    }
+                                }
+                            }
+                            label {
+                                "for": "p1r",
+                                "Seed"
                             }
                             br {}
                             if board.read().player() == Player::P1 {
@@ -266,11 +318,14 @@ fn App(cx: Scope) -> Element {
                 if *p2.get() == PlayerType::Cpu {
                     rsx! {
                         p {"{m2}"},
+                        p {"PV: {m2.pv()}"},
+                        p {"Search: {e2.read().info()}"},
                         div {
                             input {
                                 r#type: "number",
                                 id: "p2t",
                                 "min": 1,
+                                "step": 100,
                                 value: "{p2t}",
                                 oninput: move |evt| {
                                     if !evt.value.is_empty() {
@@ -279,36 +334,72 @@ fn App(cx: Scope) -> Element {
                                             Ok(num) => num
                                         };
                                         p2t.set(n);
-                                        e2.with_mut(|e2| e2.set_time(n))
+                                        e2.with_mut(|e2| e2.set_movetime(Duration::from_millis(n)))
                                     }
                                 }
                             }
                             label {
                                 "for": "p2t",
-                                "Time (s)"
+                                "Time (ms)"
                             }
                             br {}
                             input {
                                 r#type: "number",
                                 id: "p2m",
-                                "min": 1_000,
-                                "max": 1_000_000,
-                                "step": 50_000,
+                                "min": 1,
+                                "max": 1024,
+                                "step": 16,
                                 value: "{p2m}",
                                 oninput: move |evt| {
                                     if !evt.value.is_empty() {
                                         let n: usize = match evt.value.parse() {
-                                            Err(_) => DEFAULT_TABLE_SIZE,
+                                            Err(_) => DEFAULT_TABLE_MB,
                                             Ok(num) => num
                                         };
                                         p2m.set(n);
-                                        e2.with_mut(|e2| e2.set_table(n))
+                                        e2.with_mut(|e2| e2.set_table(n << 20))
                                     }
                                 }
                             }
                             label {
                                 "for": "p2m",
-                                "Table size (entrys)"
+                                "Table size (MB, {e2.read().table_entries()} entries)"
+                            }
+                            br {}
+                            input {
+                                r#type: "number",
+                                id: "p2s",
+                                "min": 0,
+                                "max": 10,
+                                value: "{p2s}",
+                                oninput: move |evt| {
+                                    if let Ok(n) = evt.value.parse::<u8>() {
+                                        let n = n.min(MAX_SKILL);
+                                        p2s.set(n);
+                                        e2.with_mut(|e2| e2.set_skill(Skill::new(n, *p2r.get())))
+                                    }
+                                }
+                            }
+                            label {
+                                "for": "p2s",
+                                "Skill (0-{MAX_SKILL})"
+                            }
+                            br {}
+                            input {
+                                r#type: "number",
+                                id: "p2r",
+                                "min": 0,
+                                value: "{p2r}",
+                                oninput: move |evt| {
+                                    if let Ok(n) = evt.value.parse::<u64>() {
+                                        p2r.set(n);
+                                        e2.with_mut(|e2| e2.set_skill(Skill::new(*p2s.get(), n)))
+                                    }
+                                }
+                            }
+                            label {
+                                "for": "p2r",
+                                "Seed"
                             }
                             br {}
                             if board.read().player() == Player::P2 {
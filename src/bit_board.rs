@@ -1,96 +1,103 @@
 use crate::board::*;
 use std::hash::Hash;
 
-#[cfg(not(target_family = "wasm"))]
-use serde::{Deserialize, Serialize};
-
-const INIT_BITBOARD: u64 =
-    0b0_110_110_110_110_110_110_110_000000000000000000000000000000000000000000;
-
-#[cfg(not(target_family = "wasm"))]
-#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug, PartialOrd, Ord, Serialize, Deserialize)]
+/// Stones in the layout used by the move generation and the win checks:
+/// one bit per cell at `col * H1 + row`, with an always empty sentinel row
+/// on top of each column so shifts never wrap between columns.
+/// `mask` holds every stone, `p1` the ones of the first player.
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug, PartialOrd, Ord, Default)]
 pub struct BitBoard {
-    board: u64,
+    p1: u64,
+    mask: u64,
 }
 
-#[cfg(target_family = "wasm")]
-#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug, PartialOrd, Ord)]
-pub struct BitBoard {
-    board: u64,
+const H1: u64 = ROW + 1;
+const BOTTOM: u64 = {
+    let mut b = 0;
+    let mut col = 0;
+    while col < COL {
+        b |= 0b1 << (col * H1);
+        col += 1;
+    }
+    b
+};
+const CELLS_MASK: u64 = BOTTOM * ((0b1 << ROW) - 1);
+
+/// columns up to the centre one
+const LEFT_HALF: u64 = (0b1 << ((COL / 2 + 1) * H1)) - 1;
+
+fn column_mask(col: u64) -> u64 {
+    ((0b1 << ROW) - 1) << (col * H1)
 }
 
-impl Default for BitBoard {
-    fn default() -> Self {
-        BitBoard { board: 0 }
+/// true if `stones` hold four in a row
+fn has_four(stones: u64) -> bool {
+    // vertical, horizontal and both diagonals
+    for dir in [1, H1, H1 - 1, H1 + 1] {
+        let pairs = stones & (stones >> dir);
+        if pairs & (pairs >> (2 * dir)) != 0 {
+            return true;
+        }
     }
+    false
 }
 
-const SIZE_SPACE: u64 = 3;
 impl BitBoard {
     pub fn is_full(&self) -> bool {
-        (self.board & 0b0_111_111_111_111_111_111_111_000000000000000000000000000000000000000000)
-            == 0b0_111_111_111_111_111_111_111_000000000000000000000000000000000000000000
+        self.mask == CELLS_MASK
     }
     pub fn is_empty(&self) -> bool {
-        self.board == INIT_BITBOARD
+        self.mask == 0
     }
     pub fn get_space(&self, col: u64) -> u64 {
-        let offset = COL * ROW + SIZE_SPACE * col;
-        let mut bits: u64 = 0b111 << offset;
-        bits &= self.board;
-        bits >>= offset;
-        bits
+        ROW - (self.mask & column_mask(col)).count_ones() as u64
     }
     pub fn get_space_array(&self) -> [u64; 7] {
         let mut arr: [u64; 7] = [0; 7];
-        for i in 0..7 {
-            arr[i] = self.get_space(i as u64);
+        for (i, space) in arr.iter_mut().enumerate() {
+            *space = self.get_space(i as u64);
         }
         arr
     }
-    fn inc_space(&mut self, col: u64) {
-        let offset = COL * ROW + SIZE_SPACE * col;
-        let inc: u64 = 0b1 << offset;
-        self.board += inc;
-    }
-    fn dec_space(&mut self, col: u64) {
-        let offset = COL * ROW + SIZE_SPACE * col;
-        let inc: u64 = 0b1 << offset;
-        self.board -= inc;
-    }
-    fn set_stone(&mut self, col: u64, row: u64, player: &Player) {
-        let offset = row * COL + col;
-        match player {
-            Player::P2 => self.board ^= 0b1 << offset,
-            Player::P1 => (),
+
+    pub fn make_move(&mut self, col: u64, player: &Player) {
+        let cell = (self.mask + (0b1 << (col * H1))) & column_mask(col);
+        self.mask |= cell;
+        if *player == Player::P1 {
+            self.p1 |= cell;
         }
     }
+    pub fn unmake_move(&mut self, col: u64, _player: &Player) {
+        let top = ((self.mask & column_mask(col)) + (0b1 << (col * H1))) >> 1;
+        self.mask ^= top;
+        self.p1 &= !top;
+    }
 
-    pub fn make_move(&mut self, col: u64, player: &Player) {
-        self.set_stone(col, ROW - self.get_space(col), player);
-        self.dec_space(col);
+    /// true if `player` has four in a row
+    pub fn is_win(&self, player: &Player) -> bool {
+        has_four(self.stones_of(player))
     }
-    pub fn unmake_move(&mut self, col: u64, player: &Player) {
-        self.inc_space(col);
-        self.set_stone(col, ROW - self.get_space(col), player);
+
+    /// owner of the stone at (`col`, `row`), None for an empty cell
+    pub fn cell(&self, col: u64, row: u64) -> Option<Player> {
+        let bit = 0b1 << (col * H1 + row);
+        if self.mask & bit == 0 {
+            None
+        } else if self.p1 & bit != 0 {
+            Some(Player::P1)
+        } else {
+            Some(Player::P2)
+        }
     }
 
     pub fn print(&self) {
-        let spacearr = self.get_space_array();
-
-        println!("board {:#b}", self.board);
-        for row in (0..6).rev() {
-            for col in 0..7 {
-                if ROW - spacearr[col] <= row {
-                    print!(". ");
-                } else {
-                    let offset: u64 = row * COL + (col as u64);
-                    let bit = (self.board & (0b1 << offset)) >> (offset);
-                    if bit == 0 {
-                        print!("O ");
-                    } else {
-                        print!("X ");
-                    }
+        println!("board {:#b} {:#b}", self.p1, self.mask);
+        for row in (0..ROW).rev() {
+            for col in 0..COL {
+                match self.cell(col, row) {
+                    None => print!(". "),
+                    Some(Player::P1) => print!("O "),
+                    Some(Player::P2) => print!("X "),
                 }
             }
             println!(" ");
@@ -98,21 +105,94 @@ impl BitBoard {
     }
 
     pub fn new() -> BitBoard {
-        BitBoard {
-            board: INIT_BITBOARD,
+        BitBoard { p1: 0, mask: 0 }
+    }
+
+    /// unique number for the position, the stones of P1 added to all the
+    /// stones never carry out of a column; see `DoubleBitBoard::canonical_key`
+    /// for the stable format
+    pub fn key(&self) -> u64 {
+        self.p1 + self.mask
+    }
+
+    /// `key` with a marker bit on top of every column, never 0 like the
+    /// check value of an empty table slot
+    pub fn table_key(&self) -> u64 {
+        self.key() + BOTTOM
+    }
+
+    fn stones_of(&self, player: &Player) -> u64 {
+        match player {
+            Player::P1 => self.p1,
+            Player::P2 => self.p1 ^ self.mask,
         }
     }
+}
+
+/// empty cells that would complete a four for the owner of `stones`
+fn winning_cells(stones: u64, mask: u64) -> u64 {
+    // vertical
+    let mut r = (stones << 1) & (stones << 2) & (stones << 3);
 
-    #[allow(dead_code)]
-    pub fn board(&self) -> u64 {
-        self.board
+    // horizontal and both diagonals
+    for dir in [H1, H1 - 1, H1 + 1] {
+        let mut p = (stones << dir) & (stones << (2 * dir));
+        r |= p & (stones << (3 * dir));
+        r |= p & (stones >> dir);
+        p = (stones >> dir) & (stones >> (2 * dir));
+        r |= p & (stones << dir);
+        r |= p & (stones >> (3 * dir));
     }
+    r & (CELLS_MASK ^ mask)
 }
 
+/// columns of the cells set in a bitmask of the `BitBoard` layout
+pub fn columns(cells: u64) -> impl Iterator<Item = u8> {
+    (0..COL as u8).filter(move |col| cells & (((0b1 << H1) - 1) << (*col as u64 * H1)) != 0)
+}
+
+const CELLS: usize = (COL * ROW) as usize;
+
+const fn splitmix64(state: u64) -> u64 {
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// Random keys for every (player, cell), a position hash is the xor of the
+/// keys of its stones
+const ZOBRIST: [[u64; CELLS]; 2] = {
+    let mut keys = [[0; CELLS]; 2];
+    let mut state: u64 = 0;
+    let mut p = 0;
+    while p < 2 {
+        let mut cell = 0;
+        while cell < CELLS {
+            state = state.wrapping_add(0x9e3779b97f4a7c15);
+            keys[p][cell] = splitmix64(state);
+            cell += 1;
+        }
+        p += 1;
+    }
+    keys
+};
+
+fn zobrist(col: u64, row: u64, player: &Player) -> u64 {
+    let p = match player {
+        Player::P1 => 0,
+        Player::P2 => 1,
+    };
+    ZOBRIST[p][(row * COL + col) as usize]
+}
+
+/// Board with its mirrored image and their incremental hashes
 #[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
 pub struct DoubleBitBoard {
-    pub(crate) normal: BitBoard,
-    pub(crate) mirrored: BitBoard,
+    normal: BitBoard,
+    mirrored: BitBoard,
+    hash: u64,
+    hash_mirrored: u64,
 }
 
 impl DoubleBitBoard {
@@ -120,7 +200,22 @@ impl DoubleBitBoard {
         DoubleBitBoard {
             normal: BitBoard::new(),
             mirrored: BitBoard::new(),
+            hash: 0,
+            hash_mirrored: 0,
+        }
+    }
+
+    /// rebuild both orientations and hashes from a single board
+    pub fn from_bitboard(board: BitBoard) -> Self {
+        let mut out = DoubleBitBoard::new();
+        for col in 0..COL {
+            for row in 0..ROW {
+                if let Some(player) = board.cell(col, row) {
+                    out.make_move(col, &player);
+                }
+            }
         }
+        out
     }
     pub fn is_full(&self) -> bool {
         self.normal.is_full()
@@ -134,13 +229,19 @@ impl DoubleBitBoard {
     pub fn get_space_array(&self) -> [u64; 7] {
         self.normal.get_space_array()
     }
+    fn toggle_hash(&mut self, col: u64, row: u64, player: &Player) {
+        self.hash ^= zobrist(col, row, player);
+        self.hash_mirrored ^= zobrist((COL - 1) - col, row, player);
+    }
     pub fn make_move(&mut self, col: u64, player: &Player) {
+        self.toggle_hash(col, ROW - self.normal.get_space(col), player);
         self.normal.make_move(col, player);
         self.mirrored.make_move((COL - 1) - col, player);
     }
     pub fn unmake_move(&mut self, col: u64, player: &Player) {
         self.normal.unmake_move(col, player);
         self.mirrored.unmake_move((COL - 1) - col, player);
+        self.toggle_hash(col, ROW - self.normal.get_space(col), player);
     }
     pub fn print(&self) {
         self.normal.print();
@@ -149,45 +250,277 @@ impl DoubleBitBoard {
     pub fn board(&self) -> BitBoard {
         self.normal
     }
+
+    /// cells where the next stone can go
+    pub fn playable(&self) -> u64 {
+        (self.normal.mask + BOTTOM) & CELLS_MASK
+    }
+
+    /// true if `player` has four in a row
+    pub fn is_win(&self, player: &Player) -> bool {
+        self.normal.is_win(player)
+    }
+
+    /// empty cells that would complete a four for `player`, playable or not
+    pub fn winning_cells(&self, player: &Player) -> u64 {
+        winning_cells(self.normal.stones_of(player), self.normal.mask)
+    }
+
+    /// playable cells for `player` that do not let the opponent complete a
+    /// four at once: the only block of an opponent four, never a cell right
+    /// under one; 0 if every move loses
+    pub fn non_losing_moves(&self, player: &Player) -> u64 {
+        let opponent_win = self.winning_cells(&player.other());
+        let mut possible = self.playable();
+        let forced = possible & opponent_win;
+        if forced != 0 {
+            if forced & (forced - 1) != 0 {
+                // two cells to block
+                return 0;
+            }
+            possible = forced;
+        }
+        possible & !(opponent_win >> 1)
+    }
+
     pub fn board_mirrored(&self) -> BitBoard {
         self.mirrored
     }
+
+    /// Key of the position and of its mirror image, the one stored in data
+    /// files. It does not depend on the board internals:
+    /// each column takes 7 bits, column `c` from bit `7 * c`, and holds the
+    /// P1 stones added to all the stones, with row `r` at bit `r` of the
+    /// column (2^h - 1 + P1 stones for a column of height h). The smaller of
+    /// the keys of the two orientations is the canonical one.
+    pub fn canonical_key(&self) -> u64 {
+        self.normal.key().min(self.mirrored.key())
+    }
+
+    /// true if the position is its own mirror image
+    pub fn is_symmetric(&self) -> bool {
+        self.normal == self.mirrored
+    }
+
+    /// `cells` without the ones right of the centre column when the
+    /// position is symmetric, their mirrored twins have the same value
+    pub fn prune_symmetric(&self, cells: u64) -> u64 {
+        if self.is_symmetric() {
+            cells & LEFT_HALF
+        } else {
+            cells
+        }
+    }
+
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+    pub fn hash_mirrored(&self) -> u64 {
+        self.hash_mirrored
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::BitBoard;
+    use super::{BitBoard, DoubleBitBoard};
 
     #[test]
     fn init() {
+        use crate::board::Player;
+
         let mut b: BitBoard = BitBoard::new();
         assert_eq!(b.get_space(6), 6);
         assert_eq!(b.get_space(0), 6);
 
-        b.make_move(3, &crate::board::Player::P1);
+        b.make_move(3, &Player::P1);
         assert_eq!(b.get_space(3), 5);
-        b.unmake_move(3, &crate::board::Player::P1);
+        b.unmake_move(3, &Player::P1);
         assert_eq!(b.get_space(3), 6);
 
+        b.make_move(0, &Player::P1);
+        b.make_move(0, &Player::P2);
         b.print();
-        b.make_move(0, &crate::board::Player::P1);
-        assert_eq!(
-            b.board,
-            0b0_110_110_110_110_110_110_101_000000000000000000000000000000000000000000
-        );
-        b.print();
-        b.make_move(0, &crate::board::Player::P2);
-        assert_eq!(
-            b.board,
-            0b0_110_110_110_110_110_110_100_000000000000000000000000000000000010000000
-        );
-        b.print();
-        b.unmake_move(0, &crate::board::Player::P2);
+        assert_eq!(b.cell(0, 0), Some(Player::P1));
+        assert_eq!(b.cell(0, 1), Some(Player::P2));
+        assert_eq!(b.cell(0, 2), None);
+        b.unmake_move(0, &Player::P2);
+        assert_eq!(b.cell(0, 1), None);
+        b.unmake_move(0, &Player::P1);
+        assert_eq!(b, BitBoard::new());
+    }
+
+    #[test]
+    fn win() {
+        use crate::board::Player::{P1, P2};
+
+        let diagonal = [
+            (0, P1),
+            (1, P2),
+            (1, P1),
+            (2, P2),
+            (2, P2),
+            (2, P1),
+            (3, P2),
+            (3, P2),
+            (3, P2),
+            (3, P1),
+        ];
+        let anti_diagonal = diagonal.map(|(col, player)| (3 - col, player));
+        for moves in [
+            &[(0, P1), (0, P1), (0, P1), (0, P1)][..],
+            &[(3, P1), (4, P1), (5, P1), (6, P1)][..],
+            &diagonal[..],
+            &anti_diagonal[..],
+        ] {
+            let mut b = BitBoard::new();
+            let (last, moves) = moves.split_last().unwrap();
+            for (col, player) in moves {
+                b.make_move(*col, player);
+            }
+            assert!(!b.is_win(&P1));
+            b.make_move(last.0, &last.1);
+            assert!(b.is_win(&P1));
+            assert!(!b.is_win(&P2));
+        }
+    }
+
+    #[test]
+    fn hash() {
+        use crate::board::Player;
+
+        let mut a = DoubleBitBoard::new();
+        a.make_move(0, &Player::P1);
+        a.make_move(1, &Player::P2);
+        a.make_move(2, &Player::P1);
+        let mut b = DoubleBitBoard::new();
+        b.make_move(2, &Player::P1);
+        b.make_move(1, &Player::P2);
+        b.make_move(0, &Player::P1);
+        assert_eq!(a.hash(), b.hash());
+        assert_eq!(a, DoubleBitBoard::from_bitboard(a.board()));
+
+        let mut m = DoubleBitBoard::new();
+        m.make_move(6, &Player::P1);
+        m.make_move(5, &Player::P2);
+        m.make_move(4, &Player::P1);
+        assert_eq!(a.hash(), m.hash_mirrored());
+
+        a.unmake_move(2, &Player::P1);
+        a.unmake_move(1, &Player::P2);
+        a.unmake_move(0, &Player::P1);
+        assert_eq!(a, DoubleBitBoard::new());
+    }
+
+    #[test]
+    fn canonical_key() {
+        use crate::board::Player;
+
+        let mut a = DoubleBitBoard::new();
+        a.make_move(0, &Player::P1);
+        a.make_move(0, &Player::P2);
+        a.make_move(1, &Player::P1);
+        // column 0: 0b11 + 0b01, column 1: 0b1 + 0b1
+        assert_eq!(a.board().key(), 0b0000010_0000100);
+
+        let mut m = DoubleBitBoard::new();
+        m.make_move(6, &Player::P1);
+        m.make_move(6, &Player::P2);
+        m.make_move(5, &Player::P1);
+        assert_eq!(a.canonical_key(), m.canonical_key());
+        assert_eq!(a.canonical_key(), a.board().key());
+
+        // same stones, other colours
+        let mut b = DoubleBitBoard::new();
+        b.make_move(0, &Player::P2);
+        b.make_move(0, &Player::P1);
+        b.make_move(1, &Player::P1);
+        assert_ne!(a.canonical_key(), b.canonical_key());
+    }
+
+    #[test]
+    fn symmetric() {
+        use super::columns;
+        use crate::board::Player;
+
+        let mut b = DoubleBitBoard::new();
+        assert!(b.is_symmetric());
+        let all = b.playable();
         assert_eq!(
-            b.board,
-            0b0_110_110_110_110_110_110_101_000000000000000000000000000000000000000000
+            columns(b.prune_symmetric(all)).collect::<Vec<_>>(),
+            [0, 1, 2, 3]
         );
-        b.unmake_move(0, &crate::board::Player::P1);
-        assert_eq!(b.board, super::INIT_BITBOARD);
+        b.make_move(3, &Player::P1);
+        b.make_move(3, &Player::P2);
+        assert!(b.is_symmetric());
+        b.make_move(2, &Player::P1);
+        assert!(!b.is_symmetric());
+        assert_eq!(b.prune_symmetric(all), all);
+    }
+
+    #[test]
+    fn winning_cells() {
+        use super::columns;
+        use crate::board::Player;
+
+        let mut b = DoubleBitBoard::new();
+        // P1: horizontal 1-2-3 on the bottom row, P2 stacks on column 6
+        for col in [1, 2, 3] {
+            b.make_move(col, &Player::P1);
+            b.make_move(6, &Player::P2);
+        }
+        let p1 = b.winning_cells(&Player::P1);
+        assert_eq!(columns(p1 & b.playable()).collect::<Vec<_>>(), [0, 4]);
+        let p2 = b.winning_cells(&Player::P2);
+        assert_eq!(columns(p2 & b.playable()).collect::<Vec<_>>(), [6]);
+
+        // P1 diagonal / (0,0) (1,1) (2,2) and row 1 (1,1) (2,1) (3,1)
+        let mut b = DoubleBitBoard::new();
+        for (col, player) in [
+            (0, Player::P1),
+            (1, Player::P2),
+            (1, Player::P1),
+            (2, Player::P2),
+            (2, Player::P1),
+            (2, Player::P1),
+            (3, Player::P2),
+            (3, Player::P1),
+            (3, Player::P2),
+        ] {
+            b.make_move(col, &player);
+        }
+        let p1 = b.winning_cells(&Player::P1);
+        assert_eq!(columns(p1 & b.playable()).collect::<Vec<_>>(), [0, 3]);
+        // (4,1) needs a stone under it first
+        assert_eq!(columns(p1 & !b.playable()).collect::<Vec<_>>(), [4]);
+    }
+
+    #[test]
+    fn non_losing_moves() {
+        use super::columns;
+        use crate::board::Player;
+
+        // P2 has three on the bottom row open on both sides
+        let mut b = DoubleBitBoard::new();
+        for (p1, p2) in [(6, 1), (6, 2), (5, 3)] {
+            b.make_move(p1, &Player::P1);
+            b.make_move(p2, &Player::P2);
+        }
+        assert_eq!(b.non_losing_moves(&Player::P1), 0);
+        // open on one side only, the block is forced
+        b.make_move(4, &Player::P1);
+        b.make_move(5, &Player::P2);
+        let moves = b.non_losing_moves(&Player::P1);
+        assert_eq!(columns(moves).collect::<Vec<_>>(), [0]);
+
+        let mut b = DoubleBitBoard::new();
+        for col in [1, 2, 3] {
+            b.make_move(col, &Player::P1);
+            b.make_move(col, &Player::P2);
+        }
+        b.make_move(6, &Player::P1);
+        b.make_move(6, &Player::P2);
+        // P2 threatens (0,1) and (4,1), playing under them loses
+        let moves = b.non_losing_moves(&Player::P1);
+        assert_eq!(columns(moves).collect::<Vec<_>>(), [1, 2, 3, 5, 6]);
     }
 }
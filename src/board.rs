@@ -1,6 +1,7 @@
 use crate::bit_board::*;
 use crate::score::*;
 use crate::score_board::*;
+use std::ops::{Deref, DerefMut};
 
 pub const COL: u64 = 7;
 pub const ROW: u64 = 6;
@@ -21,22 +22,82 @@ pub enum Player {
     P2,
 }
 
-#[derive(Clone, PartialEq)]
+impl Player {
+    pub fn other(self) -> Player {
+        match self {
+            Player::P1 => Player::P2,
+            Player::P2 => Player::P1,
+        }
+    }
+}
+
+const CELLS: usize = (COL * ROW) as usize;
+
+#[derive(Clone, Copy, PartialEq)]
 struct MoveStack {
-    moves: Vec<u8>,
+    moves: [u8; CELLS],
+    len: u8,
 }
 
 impl MoveStack {
     pub fn push_move(&mut self, col: u8) {
-        self.moves.push(col);
+        self.moves[self.len as usize] = col;
+        self.len += 1;
     }
     pub fn pop_move(&mut self) -> u8 {
-        self.moves.pop().unwrap()
+        self.len -= 1;
+        self.moves[self.len as usize]
     }
     pub fn new() -> MoveStack {
         MoveStack {
-            moves: Vec::with_capacity((COL * ROW) as usize),
+            moves: [0; CELLS],
+            len: 0,
+        }
+    }
+}
+
+/// Columns to play, a list that never needs the heap
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct MoveList {
+    cols: [u8; COL as usize],
+    len: u8,
+}
+
+impl MoveList {
+    pub fn push(&mut self, col: u8) {
+        self.cols[self.len as usize] = col;
+        self.len += 1;
+    }
+}
+
+impl Deref for MoveList {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        &self.cols[..self.len as usize]
+    }
+}
+
+impl DerefMut for MoveList {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.cols[..self.len as usize]
+    }
+}
+
+impl FromIterator<u8> for MoveList {
+    fn from_iter<I: IntoIterator<Item = u8>>(iter: I) -> MoveList {
+        let mut list = MoveList::default();
+        for col in iter {
+            list.push(col);
         }
+        list
+    }
+}
+
+impl IntoIterator for MoveList {
+    type Item = u8;
+    type IntoIter = std::iter::Take<std::array::IntoIter<u8, { COL as usize }>>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.cols.into_iter().take(self.len as usize)
     }
 }
 
@@ -44,7 +105,8 @@ impl MoveStack {
 pub struct Board {
     movestack: MoveStack,
     bitboard: DoubleBitBoard,
-    scoreboard: ScoreBoard,
+    /// incremental static evaluation, None when only exact results matter
+    scoreboard: Option<ScoreBoard>,
     gamestate: GameState,
     player: Player,
 }
@@ -62,10 +124,10 @@ impl Board {
         let row = ROW - self.bitboard.get_space(col as u64);
         self.movestack.push_move(col);
         self.bitboard.make_move(col as u64, &self.player);
-        let win = self
-            .scoreboard
-            .make_move(row as usize, col as usize, &self.player);
-        if win {
+        if let Some(scoreboard) = &mut self.scoreboard {
+            scoreboard.make_move(row as usize, col as usize, &self.player);
+        }
+        if self.bitboard.is_win(&self.player) {
             match self.player {
                 Player::P1 => self.gamestate = GameState::WinP1,
                 Player::P2 => self.gamestate = GameState::WinP2,
@@ -91,24 +153,41 @@ impl Board {
         let col = self.movestack.pop_move();
         self.bitboard.unmake_move(col as u64, &self.player);
 
-        let row = ROW - self.bitboard.get_space(col as u64);
-        self.scoreboard
-            .unmake_move(row as usize, col as usize, &self.player);
+        if let Some(scoreboard) = &mut self.scoreboard {
+            let row = ROW - self.bitboard.get_space(col as u64);
+            scoreboard.unmake_move(row as usize, col as usize, &self.player);
+        }
     }
 
-    pub fn legal_moves(&self) -> Vec<u8> {
-        let mut v: Vec<u8> = Vec::with_capacity(COL as usize);
-        let spaces = self.bitboard.get_space_array();
-        for (col, i) in spaces.iter().enumerate() {
-            if *i > 0 {
-                v.push(u8::try_from(col).unwrap());
-            }
-        }
-        v
+    pub fn legal_moves(&self) -> MoveList {
+        columns(self.bitboard.playable()).collect()
+    }
+
+    /// playable cells where the side to move completes a four, in the
+    /// threat layout of `bit_board` (see `bit_board::columns`)
+    pub fn immediate_wins(&self) -> u64 {
+        self.bitboard.playable() & self.bitboard.winning_cells(&self.player)
+    }
+
+    /// playable cells where the opponent would complete a four
+    pub fn forced_blocks(&self) -> u64 {
+        self.bitboard.playable() & self.bitboard.winning_cells(&self.player.other())
+    }
+
+    /// playable cells that do not let the opponent complete a four right
+    /// after, 0 if every move does (see `DoubleBitBoard::non_losing_moves`)
+    pub fn non_losing_moves(&self) -> u64 {
+        self.bitboard.non_losing_moves(&self.player)
+    }
+
+    /// playable cells not worth a warning: the non-losing ones and the
+    /// immediate wins, 0 if every move lets the opponent win
+    pub fn safe_moves(&self) -> u64 {
+        self.non_losing_moves() | self.immediate_wins()
     }
 
     pub fn nmoves(&self) -> u8 {
-        self.movestack.moves.len() as u8
+        self.movestack.len
     }
 
     pub fn evaluate(&self) -> Score {
@@ -116,7 +195,7 @@ impl Board {
     }
 
     pub fn free_cells(&self) -> u8 {
-        7 * 6 - (self.movestack.moves.len() as u8)
+        CELLS as u8 - self.movestack.len
     }
 
     pub fn is_empty(&self) -> bool {
@@ -127,7 +206,7 @@ impl Board {
         Board {
             movestack: MoveStack::new(),
             bitboard: DoubleBitBoard::new(),
-            scoreboard: ScoreBoard::new(),
+            scoreboard: Some(ScoreBoard::new()),
             gamestate: GameState::Open,
             player: Player::P1,
         }
@@ -159,8 +238,14 @@ impl Board {
         self.gamestate
     }
 
-    pub fn scoreboard(&self) -> &ScoreBoard {
-        &self.scoreboard
+    pub fn scoreboard(&self) -> Option<&ScoreBoard> {
+        self.scoreboard.as_ref()
+    }
+
+    /// stop updating the static evaluation, open positions then evaluate
+    /// as `EQUAL`; for searches that only look at finished games
+    pub fn disable_eval(&mut self) {
+        self.scoreboard = None;
     }
 }
 
@@ -175,9 +260,35 @@ mod tests {
         b.unmake_move();
 
         assert_eq!(b.bitboard(), DoubleBitBoard::new());
-        assert_eq!(b.movestack.moves.len(), 0);
-        assert_eq!(b.scoreboard.total_score(), 0);
+        assert_eq!(b.movestack.len, 0);
+        assert_eq!(b.scoreboard().unwrap().total_score(), 0);
         assert_eq!(b.player, Player::P1);
         assert_eq!(b.gamestate, GameState::Open);
     }
+
+    #[test]
+    fn without_eval() {
+        let mut a = Board::from_position("3344");
+        let mut b = a.clone();
+        b.disable_eval();
+        for col in [5, 0, 6] {
+            a.make_move(col);
+            b.make_move(col);
+        }
+        assert_eq!(a.gamestate(), GameState::WinP1);
+        assert_eq!(b.gamestate(), GameState::WinP1);
+        assert_eq!(b.evaluate(), a.evaluate());
+        a.unmake_move();
+        b.unmake_move();
+        assert_eq!(b.evaluate(), EQUAL);
+        assert_ne!(a.evaluate(), EQUAL);
+    }
+
+    #[test]
+    fn safe_moves() {
+        // P1 wins with 3, otherwise must block 6
+        let b = Board::from_position("061626");
+        let safe: Vec<u8> = columns(b.safe_moves()).collect();
+        assert_eq!(safe, [3, 6]);
+    }
 }
@@ -1,10 +1,10 @@
 use crate::bit_board::*;
+use crate::board::{GameState, Player, COL, ROW};
 use crate::score::*;
-use bincode;
 use serde::{Deserialize, Serialize};
 
 use std::fs::*;
-use std::io::BufReader;
+use std::io::{BufReader, Read};
 
 pub const PLY: u8 = 12;
 pub const GOOD_QUERY: u8 = PLY + 1;
@@ -16,44 +16,146 @@ const ENTRYS: usize = 4200899;
 const DBIN: &str = "./database/db-12ply-distance.txt";
 const DBOUT: &str = "./database/db-12ply-distance";
 
+/// first 8 bytes of a file keyed by `DoubleBitBoard::canonical_key`; older
+/// files start with the number of entries instead
+const FORMAT: u64 = u64::from_le_bytes(*b"c4wdb\0\0\x01");
+
+// Keys of the older files: a colour bit per cell at `row * COL + col`
+// (1 for P2) and the free space of each column in 3 bits on top
+const LEGACY_SPACE: u64 = COL * ROW;
+
+fn legacy_board(key: u64) -> BitBoard {
+    let mut b = BitBoard::new();
+    for col in 0..COL {
+        let space = (key >> (LEGACY_SPACE + 3 * col)) & 0b111;
+        for row in 0..(ROW - space) {
+            let player = if key & (0b1 << (row * COL + col)) == 0 {
+                Player::P1
+            } else {
+                Player::P2
+            };
+            b.make_move(col, &player);
+        }
+    }
+    b
+}
+
 #[derive(Clone, Default, Serialize, Deserialize, Copy)]
 struct Entry {
     score: Score,
-    key: BitBoard,
+    /// `DoubleBitBoard::canonical_key` of the position
+    key: u64,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct GameDatabase {
     data: Vec<Entry>,
+    /// open addressing index on the position key,
+    /// holds the position in `data` + 1, 0 for an empty slot
+    #[serde(skip)]
+    index: Box<[u32]>,
+}
+
+/// spread the key bits over the index slots
+fn slot(key: u64) -> usize {
+    (key.wrapping_mul(0x9e3779b97f4a7c15) >> 32) as usize
 }
 
 impl GameDatabase {
     pub fn new() -> GameDatabase {
         let file_db = File::open(DBOUT).unwrap();
-        let reader = BufReader::new(file_db);
-        let data = bincode::deserialize_from(reader).unwrap();
-        GameDatabase { data }
+        GameDatabase::read(BufReader::new(file_db)).unwrap()
     }
 
-    pub fn get(&self, key: &DoubleBitBoard) -> Option<Score> {
-        match self.data.binary_search_by_key(&key.board(), |e| e.key) {
-            Ok(index) => Some(self.data[index].score),
-            Err(_) => {
-                match self
-                    .data
-                    .binary_search_by_key(&key.board_mirrored(), |e| e.key)
-                {
-                    Ok(index) => Some(self.data[index].score),
-                    Err(_) => None,
+    /// load a database file, the keys of an older file are converted
+    fn read(mut reader: impl Read) -> bincode::Result<GameDatabase> {
+        let tag: u64 = bincode::deserialize_from(&mut reader)?;
+        let data = if tag == FORMAT {
+            bincode::deserialize_from(&mut reader)?
+        } else {
+            (0..tag)
+                .map(|_| {
+                    let e: Entry = bincode::deserialize_from(&mut reader)?;
+                    let board = DoubleBitBoard::from_bitboard(legacy_board(e.key));
+                    Ok(Entry {
+                        score: e.score,
+                        key: board.canonical_key(),
+                    })
+                })
+                .collect::<bincode::Result<Vec<Entry>>>()?
+        };
+        let mut db = GameDatabase {
+            data: Vec::new(),
+            index: Box::new([]),
+        };
+        db.set_data(data);
+        Ok(db)
+    }
+
+    fn build_index(&mut self) {
+        let slots = (self.data.len() * 2).next_power_of_two();
+        let mut index = vec![0; slots].into_boxed_slice();
+        for (i, e) in self.data.iter().enumerate() {
+            let mut slot = slot(e.key) & (slots - 1);
+            while index[slot] != 0 {
+                slot = (slot + 1) & (slots - 1);
+            }
+            index[slot] = i as u32 + 1;
+        }
+        self.index = index;
+    }
+
+    fn find(&self, key: u64) -> Option<Score> {
+        let mask = self.index.len() - 1;
+        let mut slot = slot(key) & mask;
+        loop {
+            match self.index[slot] {
+                0 => return None,
+                i => {
+                    let e = &self.data[i as usize - 1];
+                    if e.key == key {
+                        return Some(e.score);
+                    }
                 }
             }
+            slot = (slot + 1) & mask;
         }
     }
 
-    #[allow(dead_code)]
+    pub fn get(&self, key: &DoubleBitBoard) -> Option<Score> {
+        self.find(key.canonical_key())
+    }
+
+    /// database of the given positions, scores as stored in a file
+    #[cfg(test)]
+    pub fn with_positions(positions: &[(DoubleBitBoard, Score)]) -> GameDatabase {
+        let data = positions
+            .iter()
+            .map(|(board, score)| Entry {
+                score: *score,
+                key: board.canonical_key(),
+            })
+            .collect();
+        let mut db = GameDatabase {
+            data: Vec::new(),
+            index: Box::new([]),
+        };
+        db.set_data(data);
+        db
+    }
+
     fn set_data(&mut self, mut data: Vec<Entry>) {
+        // older files hold draws out of the draw scores, but a draw always
+        // ends with a full board
+        for e in data
+            .iter_mut()
+            .filter(|e| e.score.gamestate() == GameState::Open)
+        {
+            e.score = MINDRAW;
+        }
         data.sort_by_key(|e| e.key);
         self.data = data;
+        self.build_index();
     }
 }
 
@@ -74,7 +176,8 @@ mod test {
         Ok(io::BufReader::new(file).lines())
     }
 
-    fn line_to_entry(line: &str) -> Entry {
+    /// entry of a line of the text database and its position
+    fn line_to_entry(line: &str) -> (Entry, BitBoard) {
         let token: Vec<&str> = line.split(" ").collect();
 
         let mut b: BitBoard = BitBoard::new();
@@ -92,17 +195,15 @@ mod test {
 
         let score: Score;
         if s == 0 {
-            score = DRAW + dist;
+            score = MINDRAW;
         } else if s > 0 {
             score = W1 - dist;
         } else {
             score = W2 + dist;
         }
 
-        return Entry {
-            score: score,
-            key: b,
-        };
+        let key = DoubleBitBoard::from_bitboard(b).canonical_key();
+        (Entry { score, key }, b)
     }
 
     // RUST_MIN_STACK=10485760000 cargo test make_game_database -- --nocapture
@@ -115,17 +216,20 @@ mod test {
             // Consumes the iterator, returns an (Optional) String
             for (i, line) in lines.enumerate() {
                 if let Ok(ip) = line {
-                    let e = line_to_entry(ip.as_str());
+                    let (e, _) = line_to_entry(ip.as_str());
                     data.push(e);
                 }
                 nlines = i;
             }
         }
-        let mut gd = GameDatabase { data: Vec::new() };
+        let mut gd = GameDatabase {
+            data: Vec::new(),
+            index: Box::new([]),
+        };
         gd.set_data(data);
 
         let mut fout = BufWriter::new(File::create(DBOUT).unwrap());
-        bincode::serialize_into(&mut fout, &gd).unwrap();
+        bincode::serialize_into(&mut fout, &(FORMAT, &gd)).unwrap();
 
         assert_eq!(ENTRYS - 1, nlines)
     }
@@ -133,11 +237,72 @@ mod test {
     #[test]
     fn getto() {
         let db = GameDatabase::new();
-        let e = line_to_entry("1.....12112.212212. 79");
-        let bb = DoubleBitBoard {
-            normal: e.key,
-            mirrored: e.key,
-        };
+        let (e, board) = line_to_entry("1.....12112.212212. 79");
+        let bb = DoubleBitBoard::from_bitboard(board);
         assert_eq!(db.get(&bb).unwrap(), e.score)
     }
+
+    #[test]
+    fn index() {
+        let mut db = GameDatabase {
+            data: Vec::new(),
+            index: Box::new([]),
+        };
+        let (e1, _) = line_to_entry("1.....12112.212212. 79");
+        let (e2, board) = line_to_entry("1.....11121.221222. 75");
+        db.set_data(vec![e1, e2]);
+
+        let bb = DoubleBitBoard::from_bitboard(board);
+        assert_eq!(db.get(&bb).unwrap(), e2.score);
+        // stored position seen from the other side
+        let mirrored = DoubleBitBoard::from_bitboard(bb.board_mirrored());
+        assert_eq!(db.get(&mirrored).unwrap(), e2.score);
+        assert!(db.get(&DoubleBitBoard::new()).is_none());
+    }
+
+    #[test]
+    // grouped by the 3-bit column space fields
+    #[allow(clippy::unusual_byte_groupings)]
+    fn legacy() {
+        let mut b: BitBoard = BitBoard::new();
+        b.make_move(0, &Player::P1);
+        b.make_move(0, &Player::P2);
+        let key = 0b0_110_110_110_110_110_110_100_000000000000000000000000000000000010000000;
+        assert_eq!(legacy_board(key), b);
+
+        // a file without the format tag has keys of the old layout
+        let old = bincode::serialize(&vec![Entry {
+            score: W1 - 20,
+            key,
+        }])
+        .unwrap();
+        let db = GameDatabase::read(old.as_slice()).unwrap();
+        assert_eq!(db.get(&DoubleBitBoard::from_bitboard(b)), Some(W1 - 20));
+
+        let (e, board) = line_to_entry("1.....11121.221222. 75");
+        let mut gd = GameDatabase {
+            data: Vec::new(),
+            index: Box::new([]),
+        };
+        gd.set_data(vec![e]);
+        let new = bincode::serialize(&(FORMAT, &gd)).unwrap();
+        let db = GameDatabase::read(new.as_slice()).unwrap();
+        assert_eq!(db.get(&DoubleBitBoard::from_bitboard(board)), Some(e.score));
+    }
+
+    #[test]
+    fn draw() {
+        let (e, board) = line_to_entry("1.....12112.212212. 0");
+        assert_eq!(e.score, MINDRAW);
+
+        // draw of an older file, 100 moves from the end
+        let old = Entry {
+            score: DRAW + 112,
+            ..e
+        };
+        let file = bincode::serialize(&(FORMAT, vec![old])).unwrap();
+        let db = GameDatabase::read(file.as_slice()).unwrap();
+        let score = db.get(&DoubleBitBoard::from_bitboard(board)).unwrap();
+        assert_eq!(score.gamestate(), GameState::Draw);
+    }
 }
@@ -8,8 +8,11 @@ mod board;
 mod game_database;
 mod r#move;
 mod move_engine;
+mod pv;
 mod score;
 mod score_board;
+mod search_info;
+mod skill;
 mod timer;
 mod transposition_table;
 
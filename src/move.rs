@@ -2,6 +2,7 @@ use std::cmp::Ordering;
 use std::fmt::Display;
 
 use crate::board::*;
+use crate::pv::*;
 use crate::score::*;
 
 #[derive(Debug, Clone, Copy)]
@@ -9,8 +10,8 @@ pub struct Move {
     col: u8,
     player: Player,
     score: Score,
-    #[allow(dead_code)]
     depth: u8,
+    pv: Pv,
 }
 
 impl Display for Move {
@@ -26,9 +27,14 @@ impl Move {
             player,
             score,
             depth,
+            pv: Pv::new(),
         }
     }
 
+    pub fn set_pv(&mut self, pv: Pv) {
+        self.pv = pv;
+    }
+
     pub fn col(&self) -> u8 {
         self.col
     }
@@ -40,13 +46,37 @@ impl Move {
     pub fn player(&self) -> Player {
         self.player
     }
+
+    /// depth of the search that produced the score
+    pub fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    /// the score is the end of the game, not an estimate
+    pub fn is_proven(&self) -> bool {
+        self.score.gamestate() != GameState::Open
+    }
+
+    /// the same move on the mirror image of the board
+    pub fn mirrored(&self) -> Move {
+        Move {
+            col: (COL as u8 - 1) - self.col,
+            pv: self.pv.mirrored(),
+            ..*self
+        }
+    }
+
+    /// expected line starting with this move, empty if not searched yet
+    pub fn pv(&self) -> &Pv {
+        &self.pv
+    }
 }
 
 impl Eq for Move {}
 
 impl PartialEq for Move {
     fn eq(&self, other: &Self) -> bool {
-        self == other
+        self.score == other.score
     }
 }
 
@@ -1,194 +1,711 @@
+use crate::bit_board::columns;
 use crate::board::*;
 #[cfg(not(target_family = "wasm"))]
 use crate::game_database::*;
+use crate::pv::*;
 use crate::r#move::*;
 use crate::score::*;
+use crate::search_info::*;
+use crate::skill::*;
 use crate::timer::*;
 use crate::transposition_table::*;
 
+use std::cmp::Reverse;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+const PLIES: usize = (COL * ROW) as usize;
+
+/// memory of the table of a deterministic search, whatever the engine one
+const DETERMINISTIC_TABLE: usize = 16 << 20;
+
+/// Result of `Engine::solve`, scores in the P1-positive convention
+#[derive(Debug, Clone, Copy)]
+pub struct Solution {
+    /// exact score if proven, otherwise an estimate between the bounds
+    pub score: Score,
+    pub lower: Score,
+    pub upper: Score,
+    pub proven: bool,
+    /// moves left until the end of the game, only for proven scores
+    pub distance: Option<u8>,
+    pub pv: Pv,
+}
+
+/// Result of `Engine::weak_solve` for the side to move
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Win,
+    Draw,
+    Loss,
+}
+
+/// Shared flag to stop a running search from another thread, a signal
+/// handler or a UI; the search returns the best move of the last completed
+/// depth. It is cleared when the next search starts.
+#[derive(Clone, Default)]
+pub struct StopHandle(Arc<AtomicBool>);
+
+impl StopHandle {
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn reset(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Called each time the main search thread completes a depth, with the best
+/// move so far and the statistics up to that depth
+pub type Progress = Box<dyn FnMut(&Move, &SearchInfo)>;
+
 pub struct Engine {
     timer: Timer,
-    table: Table,
+    table: Arc<Table>,
+    /// emptied before every deterministic search, so its result does not
+    /// depend on the previous searches nor on the table size
+    fixed_table: Option<Arc<Table>>,
+    #[cfg(not(target_family = "wasm"))]
+    database: Arc<GameDatabase>,
+    threads: usize,
+    /// statistics of the last search, all threads together
+    info: SearchInfo,
+    /// statistics at every depth completed by the main thread of the last
+    /// search
+    iterations: Vec<SearchInfo>,
+    progress: Option<Progress>,
+    halt: StopHandle,
+    /// game clock that sets the time of every search, instead of `timer`
+    clock: Option<Clock>,
+    max_depth: Option<u8>,
+    max_nodes: Option<u64>,
+    skill: Skill,
+    /// score every root move with a full window, see `analyze`
+    multipv: bool,
+    /// root moves of the last completed depth of the last search
+    root_moves: Vec<Move>,
     #[cfg(not(target_family = "wasm"))]
-    database: GameDatabase,
+    ponder: Option<Ponder>,
 }
 
-impl Engine {
-    pub fn new(seconds: u64, table_size: usize) -> Engine {
-        Engine {
-            timer: Timer::new(seconds),
-            table: Table::new(table_size),
-            #[cfg(not(target_family = "wasm"))]
-            database: GameDatabase::new(),
+/// Search of the position expected on our next move, run on the opponent's
+/// time by a background thread; a wasm build could run it in a web worker
+#[cfg(not(target_family = "wasm"))]
+struct Ponder {
+    board: Board,
+    stop: Arc<AtomicBool>,
+    thread: std::thread::JoinHandle<Search>,
+}
+
+/// State of one search thread, the threads of a lazy SMP search share the
+/// transposition table and stop together
+struct Search {
+    timer: Timer,
+    table: Arc<Table>,
+    /// None when the search must not depend on the platform
+    #[cfg(not(target_family = "wasm"))]
+    database: Option<Arc<GameDatabase>>,
+    stop: Arc<AtomicBool>,
+    halt: StopHandle,
+    max_nodes: u64,
+    skill: Skill,
+    multipv: bool,
+    /// root moves of the last completed depth, best first
+    root: Vec<Move>,
+    /// two most recent moves that caused a cutoff at every ply
+    killers: [[Option<u8>; 2]; PLIES],
+    /// cutoffs weighted by depth for (player, column, row)
+    history: [[[u64; ROW as usize]; COL as usize]; 2],
+    info: SearchInfo,
+    iterations: Vec<SearchInfo>,
+}
+
+fn player_index(player: Player) -> usize {
+    match player {
+        Player::P1 => 0,
+        Player::P2 => 1,
+    }
+}
+
+/// +1 if the player maximizes the P1-positive score, -1 otherwise
+fn sign(player: Player) -> Score {
+    match player {
+        Player::P1 => 1,
+        Player::P2 => -1,
+    }
+}
+
+/// when every move loses: any move followed by the opponent four
+fn losing_line(board: &mut Board) -> Pv {
+    let col = board.legal_moves()[0];
+    let mut line = Pv::new();
+    board.make_move(col);
+    if let Some(win) = columns(board.immediate_wins()).next() {
+        line.update(win, &Pv::new());
+    }
+    board.unmake_move();
+    let mut pv = Pv::new();
+    pv.update(col, &line);
+    pv
+}
+
+fn move_to_front(moves: &mut [u8], col: u8) {
+    if let Some(i) = moves.iter().position(|&m| m == col) {
+        moves[..=i].rotate_right(1);
+    }
+}
+
+impl Search {
+    fn check(&self) -> Result<(), TimeoutError> {
+        if self.stop.load(Ordering::Relaxed)
+            || self.halt.is_stopped()
+            || self.info.nodes >= self.max_nodes
+        {
+            return Err(TimeoutError);
         }
+        self.timer.check()?;
+        Ok(())
     }
 
-    #[allow(dead_code)]
-    pub fn set_time(&mut self, seconds: u64) {
-        self.timer.set_duration(seconds);
+    /// statistics so far
+    fn info(&self) -> SearchInfo {
+        let mut info = self.info;
+        info.elapsed = self.timer.elapsed();
+        info
     }
 
-    #[allow(dead_code)]
-    pub fn set_table(&mut self, table_size: usize) {
-        self.table.set_size(table_size);
+    /// the search is over because of a timeout, tell if the timer ran out
+    /// rather than being stopped
+    fn timeout(&mut self) {
+        self.info.timed_out = self.timer.check().is_err();
     }
 
-    fn move_sort(board: &mut Board) -> Vec<u8> {
-        let v = board.legal_moves();
-        let mut mv: Vec<(Score, u8)> = Vec::with_capacity(COL as usize);
-        let mut out: Vec<u8> = Vec::with_capacity(COL as usize);
+    /// columns of `cells`: table move first, then killers, then the rest by
+    /// history; the static evaluation is used only when there is no history
+    /// for this position
+    fn order_moves(&self, board: &mut Board, tt_move: Option<u8>, cells: u64) -> MoveList {
+        let history = &self.history[player_index(board.player())];
+        let spaces = board.bitboard().get_space_array();
+        let score = |col: u8| history[col as usize][(ROW - spaces[col as usize]) as usize];
 
-        for m in v {
-            board.make_move(m);
-            mv.push((board.evaluate(), m));
-            board.unmake_move();
+        let mut moves: MoveList = columns(cells).collect();
+        if moves.iter().all(|&col| score(col) == 0) {
+            moves = Engine::move_sort(board, cells);
+        } else {
+            moves.sort_unstable_by_key(|&col| (Reverse(score(col)), (col as i32 - 3).abs(), col));
+        }
+
+        for killer in self.killers[board.nmoves() as usize].iter().rev().flatten() {
+            move_to_front(&mut moves, *killer);
         }
-        mv.sort_by_key(|mv| mv.0);
-        if board.player() == Player::P1 {
-            mv.reverse();
+        if let Some(col) = tt_move {
+            move_to_front(&mut moves, col);
         }
+        moves
+    }
 
-        for m in mv {
-            out.push(m.1);
+    /// remember a move that caused a beta cutoff
+    fn store_cutoff(&mut self, board: &Board, col: u8, depth: u8) {
+        let killers = &mut self.killers[board.nmoves() as usize];
+        if killers[0] != Some(col) {
+            killers[1] = killers[0];
+            killers[0] = Some(col);
         }
-        out
+        let row = ROW - board.bitboard().get_space(col as u64);
+        self.history[player_index(board.player())][col as usize][row as usize] +=
+            depth as u64 * depth as u64;
     }
 
+    /// score of the position in the P1-positive convention of `score.rs`,
+    /// `pv` is filled with the line that produced it
     pub fn alpha_beta(
+        &mut self,
+        board: &mut Board,
+        alpha: Score,
+        beta: Score,
+        depth: u8,
+        pv: &mut Pv,
+    ) -> Result<Score, TimeoutError> {
+        match board.player() {
+            Player::P1 => self.negamax(board, alpha, beta, depth, pv),
+            Player::P2 => Ok(-self.negamax(board, -beta, -alpha, depth, pv)?),
+        }
+    }
+
+    /// principal variation search, scores are relative to the side to move
+    /// (also the ones stored in the table)
+    fn negamax(
         &mut self,
         board: &mut Board,
         mut alpha: Score,
         mut beta: Score,
         depth: u8,
+        pv: &mut Pv,
     ) -> Result<Score, TimeoutError> {
+        pv.clear();
+        self.info.nodes += 1;
+        let sign = sign(board.player());
+
         #[cfg(not(target_family = "wasm"))]
-        let saved_score: Option<Score> = if board.nmoves() == PLY {
-            self.database.get(&board.bitboard())
-        } else if depth >= 1 {
-            self.table.get(&board.bitboard())
-        } else {
-            None
-        };
+        let database = self.database.as_ref().filter(|_| board.nmoves() == PLY);
+        #[cfg(not(target_family = "wasm"))]
+        if let Some(database) = database {
+            if let Some(score) = database.get(&board.bitboard()) {
+                self.info.db_hits += 1;
+                return Ok(sign * score);
+            }
+        }
 
+        #[cfg(not(target_family = "wasm"))]
+        let use_table = depth >= 1 && database.is_none();
         #[cfg(target_family = "wasm")]
-        let saved_score: Option<Score> = if depth >= 1 {
-            self.table.get(&board.bitboard())
-        } else {
-            None
-        };
-
-        match saved_score {
-            Some(score) => Ok(score),
-            None => {
-                let moves: Vec<u8>;
-                let mut eval: Score;
-
-                if depth == 0 || board.gamestate() != GameState::Open {
-                    return Ok(board.evaluate());
-                } else {
-                    if depth >= 1 {
-                        moves = Self::move_sort(board);
-                    } else {
-                        moves = board.legal_moves();
-                    }
+        let use_table = depth >= 1;
 
-                    match board.player() {
-                        Player::P1 => {
-                            eval = MIN;
-                            for m in moves {
-                                self.timer.check()?;
-
-                                board.make_move(m);
-                                let newscore = self.alpha_beta(board, alpha, beta, depth - 1)?;
-                                board.unmake_move();
-
-                                eval = eval.max(newscore);
-                                alpha = alpha.max(eval);
-                                if alpha > beta {
-                                    break;
-                                }
-                            }
-                        }
-                        Player::P2 => {
-                            eval = MAX;
-                            for m in moves {
-                                self.timer.check()?;
-
-                                board.make_move(m);
-                                let newscore = self.alpha_beta(board, alpha, beta, depth - 1)?;
-                                board.unmake_move();
-
-                                eval = eval.min(newscore);
-                                beta = beta.min(eval);
-                                if alpha > beta {
-                                    break;
-                                }
-                            }
-                        }
+        let mut tt_move = None;
+        if use_table {
+            self.info.tt_probes += 1;
+            if let Some(entry) = self.table.get(&board.bitboard()) {
+                self.info.tt_hits += 1;
+                // PV nodes search on so that the whole line is known
+                let pv_node = beta - alpha > 1;
+                if let Some(score) = entry.cutoff(alpha, beta, depth).filter(|_| !pv_node) {
+                    if let Some(col) = entry.best() {
+                        pv.update(col, &Pv::new());
                     }
+                    return Ok(score);
                 }
-                #[cfg(not(target_family = "wasm"))]
-                if depth >= 1 && board.nmoves() != PLY {
-                    self.table.set(board.bitboard(), eval);
-                }
-                #[cfg(target_family = "wasm")]
-                if depth >= 1 {
-                    self.table.set(board.bitboard(), eval);
+                tt_move = entry.best();
+            }
+        }
+
+        if depth == 0 || board.gamestate() != GameState::Open {
+            return Ok(sign * board.evaluate());
+        }
+
+        let n = board.nmoves() as Score;
+        if let Some(col) = columns(board.immediate_wins()).next() {
+            pv.update(col, &Pv::new());
+            return Ok(W1 - (n + 1));
+        }
+        let non_losing = board.non_losing_moves();
+        if non_losing == 0 {
+            *pv = losing_line(board);
+            return Ok(-(W1 - (n + 2)));
+        }
+        let non_losing = board.bitboard().prune_symmetric(non_losing);
+
+        // at best we win with our next stone, at worst lose with the next
+        // opponent stone
+        let best_score = W1 - (n + 1);
+        let worst_score = -(W1 - (n + 2));
+        if beta > best_score {
+            beta = best_score;
+            if alpha >= beta {
+                return Ok(beta);
+            }
+        }
+        if alpha < worst_score {
+            alpha = worst_score;
+            if alpha >= beta {
+                return Ok(alpha);
+            }
+        }
+
+        let moves = self.order_moves(board, tt_move, non_losing);
+        let mut eval: Score = MIN;
+        let mut child_pv = Pv::new();
+        let alpha_orig = alpha;
+
+        for (i, m) in moves.into_iter().enumerate() {
+            self.check()?;
+
+            board.make_move(m);
+            let mut newscore;
+            if i == 0 {
+                newscore = -self.negamax(board, -beta, -alpha, depth - 1, &mut child_pv)?;
+            } else {
+                // prove the move is not better than the first one
+                newscore = -self.negamax(board, -alpha - 1, -alpha, depth - 1, &mut child_pv)?;
+                if newscore > alpha && newscore < beta {
+                    newscore = -self.negamax(board, -beta, -alpha, depth - 1, &mut child_pv)?;
                 }
-                Ok(eval)
             }
+            board.unmake_move();
+
+            if newscore > eval {
+                eval = newscore;
+                pv.update(m, &child_pv);
+            }
+            alpha = alpha.max(eval);
+            if alpha >= beta {
+                self.info.cutoffs += 1;
+                self.store_cutoff(board, m, depth);
+                break;
+            }
+        }
+
+        if use_table {
+            // fail-soft: a score outside the original window is only a bound
+            let bound = if eval <= alpha_orig {
+                Bound::Upper
+            } else if eval >= beta {
+                Bound::Lower
+            } else {
+                Bound::Exact
+            };
+            let best = match bound {
+                Bound::Upper => None,
+                _ => pv.moves().first().copied(),
+            };
+            self.table.set(board.bitboard(), eval, depth, bound, best);
         }
+        Ok(eval)
     }
 
+    /// score every root move, the ones already proven are kept as they are;
+    /// in a symmetric position the twin of a searched move is copied. Only
+    /// the best move gets an exact score, unless `multipv` searches all of
+    /// them with a full window
     fn move_list(
         &mut self,
         board: &mut Board,
         prev_ml: &Vec<Move>,
         depth: u8,
     ) -> Result<Vec<Move>, TimeoutError> {
+        let sign = sign(board.player());
         let mut alpha = MIN;
-        let mut beta = MAX;
+        let beta = MAX;
         let mut out: Vec<Move> = Vec::with_capacity(COL as usize);
+        let mut child_pv = Pv::new();
+        let mut pv = Pv::new();
+        let mut first = true;
+        let symmetric = board.bitboard().is_symmetric();
+        let mut searched: [Option<Move>; COL as usize] = [None; COL as usize];
 
-        match board.player() {
-            Player::P1 => {
-                for m in prev_ml {
-                    self.timer.check()?;
-                    match m.score().gamestate() {
-                        GameState::Open => {
-                            board.make_move(m.col());
-                            let newscore = self.alpha_beta(board, alpha, beta, depth - 1)?;
-                            board.unmake_move();
-
-                            out.push(Move::new(m.col(), m.player(), newscore, depth));
-                            alpha = alpha.max(newscore);
-                        }
-                        _ => {
-                            out.push(*m);
+        for m in prev_ml {
+            self.check()?;
+            let twin = searched[((COL as u8 - 1) - m.col()) as usize];
+            if let (true, Some(twin)) = (symmetric, twin) {
+                out.push(twin.mirrored());
+                continue;
+            }
+            match m.score().gamestate() {
+                GameState::Open => {
+                    board.make_move(m.col());
+                    let mut newscore;
+                    if first || self.multipv {
+                        newscore = -self.negamax(board, -beta, -alpha, depth - 1, &mut child_pv)?;
+                        first = false;
+                    } else {
+                        newscore =
+                            -self.negamax(board, -alpha - 1, -alpha, depth - 1, &mut child_pv)?;
+                        if newscore > alpha && newscore < beta {
+                            newscore =
+                                -self.negamax(board, -beta, -alpha, depth - 1, &mut child_pv)?;
                         }
                     }
+                    board.unmake_move();
+
+                    let mut score = sign * newscore;
+                    if score.gamestate() == GameState::Open {
+                        score += self.skill.noise();
+                    }
+                    let mut mv = Move::new(m.col(), m.player(), score, depth);
+                    pv.update(m.col(), &child_pv);
+                    mv.set_pv(pv);
+                    searched[m.col() as usize] = Some(mv);
+                    out.push(mv);
+                    if !self.multipv {
+                        alpha = alpha.max(newscore);
+                    }
+                }
+                _ => {
+                    out.push(*m);
                 }
             }
-            Player::P2 => {
-                for m in prev_ml {
-                    self.timer.check()?;
-                    match m.score().gamestate() {
-                        GameState::Open => {
-                            board.make_move(m.col());
-                            let newscore = self.alpha_beta(board, alpha, beta, depth - 1)?;
-                            board.unmake_move();
-
-                            out.push(Move::new(m.col(), m.player(), newscore, depth));
-                            beta = beta.min(newscore);
-                        }
-                        _ => {
-                            out.push(*m);
-                        }
+        }
+        Ok(out)
+    }
+
+    /// exact relative score by null-window binary search, searching until
+    /// the end of the game; on timeout returns the bounds found so far
+    fn solve(&mut self, board: &mut Board, pv: &mut Pv) -> (Score, Score) {
+        let depth = board.free_cells();
+        let (mut min, mut max) = (MIN, MAX);
+
+        while min < max {
+            let med = min + (max - min) / 2;
+            match self.negamax(board, med, med + 1, depth, pv) {
+                Ok(r) if r <= med => max = r,
+                Ok(r) => min = r,
+                Err(TimeoutError) => {
+                    self.timeout();
+                    return (min, max);
+                }
+            }
+        }
+        self.info.depth = depth;
+        // exact window to get the line of the best move
+        if self.negamax(board, min - 1, min + 1, depth, pv).is_err() {
+            pv.clear();
+        }
+        (min, max)
+    }
+
+    /// searching until the end of the game with a window just around the
+    /// draw scores tells who wins, but not when
+    fn weak_solve(&mut self, board: &mut Board) -> Result<GameState, TimeoutError> {
+        let depth = board.free_cells();
+        let score = match self.alpha_beta(board, DRAW - 1, MINDRAW + 1, depth, &mut Pv::new()) {
+            Ok(score) => score,
+            Err(e) => {
+                self.timeout();
+                return Err(e);
+            }
+        };
+        self.info.depth = depth;
+        Ok(if score < DRAW {
+            GameState::WinP2
+        } else if score > MINDRAW {
+            GameState::WinP1
+        } else {
+            GameState::Draw
+        })
+    }
+
+    /// iterative deepening from `min_depth` until the soft time limit,
+    /// returns the best move of the last completed depth; `progress` is
+    /// called after every depth. It stops early once the best move is
+    /// proven, or every move with `multipv`
+    fn iterate(
+        &mut self,
+        board: &Board,
+        mut movelist: Vec<Move>,
+        min_depth: u8,
+        max_depth: u8,
+        progress: &mut dyn FnMut(&Move, &SearchInfo),
+    ) -> Move {
+        let mut tb: Board = board.clone();
+        let mut bestmove: Move = movelist[0];
+
+        for i in min_depth..max_depth {
+            if i > min_depth && self.timer.soft_expired() {
+                // the next depth would hardly finish in time
+                self.info.timed_out = true;
+                break;
+            }
+            match self.move_list(&mut tb, &movelist, i) {
+                Ok(mut ml) => {
+                    ml.sort();
+                    if board.player() == Player::P1 {
+                        ml.reverse();
+                    }
+                    if i > min_depth && movelist[0].col() != ml[0].col() {
+                        // the best move is not settled yet
+                        self.timer.extend_soft();
+                    }
+                    movelist = ml;
+                    bestmove = movelist[0];
+                    self.info.depth = i;
+                    let info = self.info();
+                    self.iterations.push(info);
+                    progress(&bestmove, &info);
+
+                    let settled = match self.multipv {
+                        true => movelist.iter().all(|m| m.is_proven()),
+                        false => bestmove.is_proven(),
+                    };
+                    if settled {
+                        break;
                     }
                 }
+                Err(TimeoutError) => {
+                    self.timeout();
+                    break;
+                }
             }
         }
-        Ok(out)
+        self.root = movelist.clone();
+        if self.skill.is_handicapped()
+            && movelist.len() > 1
+            && movelist[1].score().gamestate() == GameState::Open
+            && self.skill.second_best()
+        {
+            return movelist[1];
+        }
+        bestmove
+    }
+}
+
+impl Engine {
+    /// `table_memory` is the transposition table budget in bytes
+    pub fn new(seconds: u64, table_memory: usize) -> Engine {
+        Engine {
+            timer: Timer::new(seconds),
+            table: Arc::new(Table::new(table_memory)),
+            fixed_table: None,
+            #[cfg(not(target_family = "wasm"))]
+            database: Arc::new(GameDatabase::new()),
+            threads: 1,
+            info: SearchInfo::default(),
+            iterations: Vec::new(),
+            progress: None,
+            halt: StopHandle::default(),
+            clock: None,
+            max_depth: None,
+            max_nodes: None,
+            skill: Skill::new(MAX_SKILL, 0),
+            multipv: false,
+            root_moves: Vec::new(),
+            #[cfg(not(target_family = "wasm"))]
+            ponder: None,
+        }
+    }
+
+    /// handle to stop the searches of this engine
+    pub fn stop_handle(&self) -> StopHandle {
+        self.halt.clone()
+    }
+
+    /// search until stopped through `stop_handle` or the game is solved,
+    /// `set_time` or `set_movetime` put back a time limit
+    #[allow(dead_code)]
+    pub fn set_infinite(&mut self) {
+        self.clock = None;
+        self.timer = Timer::unlimited();
+    }
+
+    /// report every completed depth of the next searches to `progress`
+    pub fn set_progress(&mut self, progress: impl FnMut(&Move, &SearchInfo) + 'static) {
+        self.progress = Some(Box::new(progress));
+    }
+
+    #[allow(dead_code)]
+    pub fn clear_progress(&mut self) {
+        self.progress = None;
+    }
+
+    #[allow(dead_code)]
+    pub fn set_time(&mut self, seconds: u64) {
+        self.clock = None;
+        self.timer.set_duration(seconds);
+    }
+
+    /// time budget for each search, see `Timer::movetime`
+    pub fn set_movetime(&mut self, budget: Duration) {
+        self.clock = None;
+        self.timer.set_movetime(budget);
+    }
+
+    /// no new depth is started after `soft`, the search is aborted at `hard`
+    #[allow(dead_code)]
+    pub fn set_limits(&mut self, soft: Duration, hard: Duration) {
+        self.clock = None;
+        self.timer = Timer::with_limits(soft, hard);
+    }
+
+    /// stop iterative deepening after `depth` plies, None for no limit
+    pub fn set_max_depth(&mut self, depth: Option<u8>) {
+        self.max_depth = depth.map(|depth| depth.max(1));
+    }
+
+    /// abort a search after `nodes` nodes, None for no limit
+    pub fn set_max_nodes(&mut self, nodes: Option<u64>) {
+        self.max_nodes = nodes;
+    }
+
+    /// play weaker, see `Skill`
+    pub fn set_skill(&mut self, skill: Skill) {
+        self.skill = skill;
+    }
+
+    /// with a depth or node limit (or a skill handicap) the result is the
+    /// same on every run and platform, unless the time runs out first: the
+    /// search takes a single thread, starts from depth 1 and does not use
+    /// the database
+    fn deterministic(&self) -> bool {
+        self.max_depth.is_some() || self.max_nodes.is_some() || self.skill.is_handicapped()
+    }
+
+    /// the tighter of the depth limit and the skill one
+    fn depth_limit(&self) -> Option<u8> {
+        match (self.max_depth, self.skill.max_depth()) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// let the engine share the clock of a timed game among its moves, to
+    /// be updated before every search
+    pub fn set_clock(&mut self, clock: Clock) {
+        self.clock = Some(clock);
+    }
+
+    #[allow(dead_code)]
+    pub fn set_table(&mut self, table_memory: usize) {
+        self.stop_ponder();
+        if let Some(table) = Arc::get_mut(&mut self.table) {
+            table.set_size(table_memory);
+        }
+    }
+
+    /// number of lazy SMP search threads, the wasm build always searches
+    /// on a single thread
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    /// statistics of the last search or solve
+    pub fn info(&self) -> &SearchInfo {
+        &self.info
+    }
+
+    /// statistics of the last search at every completed depth
+    #[allow(dead_code)]
+    pub fn iterations(&self) -> &[SearchInfo] {
+        &self.iterations
+    }
+
+    /// transposition table entries bought by the memory budget
+    pub fn table_entries(&self) -> usize {
+        self.table.entries()
+    }
+
+    /// columns of `cells` by static evaluation, or by the number of cells
+    /// they make winning when the board does not evaluate
+    fn move_sort(board: &mut Board, cells: u64) -> MoveList {
+        let mut moves: MoveList = columns(cells).collect();
+        if board.scoreboard().is_none() {
+            Self::threat_sort(board, &mut moves);
+            return moves;
+        }
+        let mut eval = [EQUAL; COL as usize];
+        for &m in moves.iter() {
+            board.make_move(m);
+            eval[m as usize] = board.evaluate();
+            board.unmake_move();
+        }
+        // best first, ties by higher column for P1 and lower for P2
+        let sign = sign(board.player());
+        moves.sort_unstable_by_key(|&m| (-sign * eval[m as usize], -sign * m as Score));
+        moves
+    }
+
+    fn threat_sort(board: &mut Board, moves: &mut MoveList) {
+        let player = board.player();
+        let mut threats = [0; COL as usize];
+        for &m in moves.iter() {
+            board.make_move(m);
+            threats[m as usize] = board.bitboard().winning_cells(&player).count_ones();
+            board.unmake_move();
+        }
+        moves.sort_unstable_by_key(|&m| (Reverse(threats[m as usize]), (m as i32 - 3).abs(), m));
     }
 
     fn init_move_array(board: &Board) -> Vec<Move> {
@@ -201,53 +718,327 @@ impl Engine {
     }
 
     fn get_ready(&mut self) {
-        self.table.get_ready();
+        self.stop_ponder();
+        if let Some(table) = Arc::get_mut(&mut self.table) {
+            table.get_ready();
+        }
+        self.table.new_search();
+        if self.deterministic() {
+            let table = self
+                .fixed_table
+                .get_or_insert_with(|| Arc::new(Table::new(DETERMINISTIC_TABLE)));
+            if let Some(table) = Arc::get_mut(table) {
+                table.clear();
+            }
+        }
         self.timer.start();
+        self.halt.reset();
+        self.info = SearchInfo::default();
+        self.iterations.clear();
+    }
+
+    fn search(&self, stop: &Arc<AtomicBool>) -> Search {
+        let table = match (self.deterministic(), &self.fixed_table) {
+            (true, Some(table)) => table,
+            _ => &self.table,
+        };
+        Search {
+            timer: self.timer.clone(),
+            table: Arc::clone(table),
+            #[cfg(not(target_family = "wasm"))]
+            database: match self.deterministic() {
+                true => None,
+                false => Some(Arc::clone(&self.database)),
+            },
+            stop: Arc::clone(stop),
+            halt: self.halt.clone(),
+            max_nodes: self.max_nodes.unwrap_or(u64::MAX),
+            skill: self.skill,
+            multipv: self.multipv,
+            root: Vec::new(),
+            killers: [[None; 2]; PLIES],
+            history: [[[0; ROW as usize]; COL as usize]; 2],
+            info: SearchInfo::default(),
+            iterations: Vec::new(),
+        }
+    }
+
+    fn solver(&mut self, seconds: Option<u64>) -> Search {
+        self.get_ready();
+        let mut search = self.search(&Arc::new(AtomicBool::new(false)));
+        search.timer = match seconds {
+            Some(seconds) => Timer::new(seconds),
+            None => Timer::unlimited(),
+        };
+        search
+    }
+
+    /// win, draw or loss for the side to move, faster than `solve`;
+    /// None if the time ran out
+    pub fn weak_solve(&mut self, board: &Board, seconds: Option<u64>) -> Option<Outcome> {
+        let mut search = self.solver(seconds);
+        let mut tb = board.clone();
+        tb.disable_eval();
+        let state = search.weak_solve(&mut tb);
+        self.info = search.info();
+        let state = state.ok()?;
+        Some(match (state, board.player()) {
+            (GameState::Draw, _) | (GameState::Open, _) => Outcome::Draw,
+            (GameState::WinP1, Player::P1) | (GameState::WinP2, Player::P2) => Outcome::Win,
+            _ => Outcome::Loss,
+        })
+    }
+
+    /// prove the value of the position, without a time limit if `seconds`
+    /// is None
+    pub fn solve(&mut self, board: &Board, seconds: Option<u64>) -> Solution {
+        let mut search = self.solver(seconds);
+
+        let mut tb = board.clone();
+        tb.disable_eval();
+        let mut pv = Pv::new();
+        let (min, max) = search.solve(&mut tb, &mut pv);
+        self.info = search.info();
+
+        let sign = sign(board.player());
+        let (lower, upper) = if sign > 0 { (min, max) } else { (-max, -min) };
+        let proven = lower == upper;
+        let score = if proven {
+            lower
+        } else {
+            EQUAL.clamp(lower, upper)
+        };
+        Solution {
+            score,
+            lower,
+            upper,
+            proven,
+            distance: match proven {
+                true => score.stones().map(|n| n - board.nmoves()),
+                false => None,
+            },
+            pv,
+        }
+    }
+
+    /// the move to play without searching: an immediate win, any move if
+    /// all of them lose at once, or the only block of an opponent four
+    /// (scored statically)
+    fn forced_move(board: &Board) -> Option<Move> {
+        let sign = sign(board.player());
+        let n = board.nmoves() as Score;
+        let mut pv = Pv::new();
+
+        let (col, score) = if let Some(col) = columns(board.immediate_wins()).next() {
+            pv.update(col, &Pv::new());
+            (col, sign * (W1 - (n + 1)))
+        } else if board.non_losing_moves() == 0 {
+            pv = losing_line(&mut board.clone());
+            (pv.moves()[0], -sign * (W1 - (n + 2)))
+        } else {
+            let col = columns(board.forced_blocks()).next()?;
+            pv.update(col, &Pv::new());
+            let mut tb = board.clone();
+            tb.make_move(col);
+            (col, tb.evaluate())
+        };
+        let mut m = Move::new(col, board.player(), score, pv.moves().len() as u8);
+        m.set_pv(pv);
+        Some(m)
     }
 
     pub fn iterative_depening(&mut self, board: &Board) -> Move {
+        if let Some(clock) = self.clock {
+            self.timer = clock.timer(board.free_cells());
+        }
+        let pondered = self.stop_ponder();
         self.get_ready();
 
-        let mut tb: Board = board.clone();
-        let mut movelist = Self::init_move_array(&tb);
-        let mut bestmove: Move = movelist[0];
+        let mut movelist = Self::init_move_array(board);
+
+        if board.is_empty() && !self.multipv {
+            let mut m = Move::new(3, board.player(), EQUAL, 0);
+            let mut pv = Pv::new();
+            pv.update(3, &Pv::new());
+            m.set_pv(pv);
+            return m;
+        }
+
+        if let Some(m) = Self::forced_move(board).filter(|_| !self.multipv) {
+            return m;
+        }
+
+        let (mut min_depth, max_depth) = self.depths(board);
 
-        if board.is_empty() {
-            return Move::new(3, board.player(), EQUAL, 0);
+        // ponder hit: answer at once if the background search is done,
+        // otherwise go on from the depth it reached
+        let key = board.bitboard().board().key();
+        let pondered = pondered.filter(|(b, _)| b.bitboard().board().key() == key && !self.multipv);
+        if let Some((_, ponder)) = pondered {
+            if let Some(&best) = ponder.root.first().filter(|m| m.depth() > 0) {
+                if best.is_proven() || best.depth() + 1 >= max_depth {
+                    self.info = ponder.info();
+                    self.iterations = ponder.iterations;
+                    return best;
+                }
+                movelist = ponder.root;
+                min_depth = min_depth.max(best.depth() + 1);
+            }
         }
 
-        let max_depth: u8 = board.free_cells();
+        let threads = match self.deterministic() {
+            true => 1,
+            false => self.threads,
+        };
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut progress = self.progress.take();
+        let mut report = |m: &Move, info: &SearchInfo| {
+            if let Some(progress) = &mut progress {
+                progress(m, info);
+            }
+        };
 
         #[cfg(not(target_family = "wasm"))]
-        let min_depth = if board.nmoves() <= PLY {
+        let (bestmove, main) = std::thread::scope(|s| {
+            // helpers start one ply deeper every other thread so they
+            // fill the table ahead of the main search
+            let helpers: Vec<_> = (1..threads)
+                .map(|i| {
+                    let mut search = self.search(&stop);
+                    let board = board.clone();
+                    let movelist = movelist.clone();
+                    let start = min_depth + (i % 2) as u8;
+                    s.spawn(move || {
+                        let m = search.iterate(&board, movelist, start, max_depth, &mut |_, _| {});
+                        (m, search.info())
+                    })
+                })
+                .collect();
+
+            let mut main = self.search(&stop);
+            let mut bestmove = main.iterate(board, movelist, min_depth, max_depth, &mut report);
+            stop.store(true, Ordering::Relaxed);
+
+            // keep the deepest completed result
+            for (m, info) in helpers.into_iter().map(|h| h.join().unwrap()) {
+                if m.depth() > bestmove.depth() {
+                    bestmove = m;
+                }
+                self.info.merge(&info);
+            }
+            (bestmove, main)
+        });
+        #[cfg(target_family = "wasm")]
+        let (bestmove, main) = {
+            let mut main = self.search(&stop);
+            let bestmove = main.iterate(board, movelist, min_depth, max_depth, &mut report);
+            (bestmove, main)
+        };
+
+        self.info.merge(&main.info());
+        self.iterations = main.iterations;
+        self.root_moves = main.root;
+        self.skill = main.skill;
+        self.progress = progress;
+        bestmove
+    }
+
+    /// first depth of a search of `board` and the depth it must not reach
+    fn depths(&self, board: &Board) -> (u8, u8) {
+        let max_depth: u8 = match self.depth_limit() {
+            Some(depth) => board.free_cells().min(depth.saturating_add(1)),
+            None => board.free_cells(),
+        };
+
+        #[cfg(not(target_family = "wasm"))]
+        let min_depth = if board.nmoves() <= PLY && !self.deterministic() && !self.multipv {
             GOOD_QUERY - board.nmoves() + 2
         } else {
             1
         };
         #[cfg(target_family = "wasm")]
         let min_depth = 1;
+        (min_depth, max_depth)
+    }
 
-        for i in min_depth..max_depth {
-            self.table.clean();
-            match self.move_list(&mut tb, &movelist, i) {
-                Ok(mut ml) => {
-                    ml.sort();
-                    if board.player() == Player::P1 {
-                        ml.reverse();
-                    }
-                    movelist = ml;
-                    bestmove = movelist[0];
-
-                    if bestmove.score().gamestate() != GameState::Open {
-                        break;
-                    }
-                }
-                Err(TimeoutError) => {
-                    return bestmove;
-                }
+    /// search on the opponent's time until the next search, `board` has
+    /// the opponent to move and `reply` is the expected answer, like the
+    /// second move of our last line. With no reply, or a reply that ends
+    /// the game, all the replies are searched: only the table gains from
+    /// it. The next search of the pondered position answers at once or
+    /// goes on from the depth reached. Deterministic engines do not ponder
+    #[cfg(not(target_family = "wasm"))]
+    pub fn ponder(&mut self, board: &Board, reply: Option<u8>) {
+        self.stop_ponder();
+        let mut board = board.clone();
+        if board.gamestate() != GameState::Open || self.deterministic() {
+            return;
+        }
+        if let Some(col) = reply.filter(|c| board.legal_moves().contains(c)) {
+            board.make_move(col);
+            if board.gamestate() != GameState::Open {
+                board.unmake_move();
             }
         }
-        bestmove
+        if let Some(table) = Arc::get_mut(&mut self.table) {
+            table.get_ready();
+        }
+        self.table.new_search();
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut search = self.search(&stop);
+        search.timer = Timer::unlimited();
+        // a stop of the last search must not stop this one
+        search.halt = StopHandle::default();
+        let (min_depth, max_depth) = self.depths(&board);
+        let movelist = Self::init_move_array(&board);
+        let thread = {
+            let board = board.clone();
+            std::thread::spawn(move || {
+                search.iterate(&board, movelist, min_depth, max_depth, &mut |_, _| {});
+                search
+            })
+        };
+        self.ponder = Some(Ponder {
+            board,
+            stop,
+            thread,
+        });
+    }
+
+    /// end the background search, if any, with what it found; nothing if
+    /// its thread panicked
+    fn stop_ponder(&mut self) -> Option<(Board, Search)> {
+        #[cfg(not(target_family = "wasm"))]
+        if let Some(ponder) = self.ponder.take() {
+            ponder.stop.store(true, Ordering::Relaxed);
+            return ponder
+                .thread
+                .join()
+                .ok()
+                .map(|search| (ponder.board, search));
+        }
+        None
+    }
+
+    /// every legal move with its score, depth and line, best first for the
+    /// side to move; all of them are searched with a full window, so this
+    /// is slower than `iterative_depening` for the same depth
+    pub fn analyze(&mut self, board: &Board) -> Vec<Move> {
+        if board.gamestate() != GameState::Open {
+            return Vec::new();
+        }
+        self.multipv = true;
+        self.iterative_depening(board);
+        self.multipv = false;
+        std::mem::take(&mut self.root_moves)
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        self.stop_ponder();
     }
 }
 
@@ -258,8 +1049,245 @@ mod tests {
     #[test]
     fn ml() {
         let mut b = Board::new();
-        let ml = Engine::move_sort(&mut b);
-        assert_eq!(ml, [3, 4, 2, 5, 1, 6, 0])
+        let all = b.non_losing_moves();
+        let ml = Engine::move_sort(&mut b, all);
+        assert_eq!(*ml, [3, 4, 2, 5, 1, 6, 0])
+    }
+
+    #[test]
+    fn symmetric() {
+        let mut board = Board::from_position("33");
+        let e = Engine::new(3, 1 << 20);
+        let mut search = e.search(&Arc::new(AtomicBool::new(false)));
+        let ml = Engine::init_move_array(&board);
+        let ml = search.move_list(&mut board, &ml, 4).unwrap();
+        assert_eq!(ml.len(), COL as usize);
+        for m in ml.iter() {
+            let twin = ml.iter().find(|t| t.col() == 6 - m.col()).unwrap();
+            assert_eq!(m.score(), twin.score());
+            assert_eq!(*m.pv(), twin.pv().mirrored());
+        }
+    }
+
+    #[test]
+    fn info() {
+        let mut e = Engine::new(1, 1 << 20);
+        let m = e.iterative_depening(&Board::from_position("3332222"));
+        let info = e.info();
+        assert!(info.nodes > 0);
+        assert!(info.tt_probes >= info.tt_hits);
+        assert_eq!(info.depth, m.depth());
+        let last = e.iterations().last().unwrap();
+        assert!(last.nodes <= info.nodes);
+        assert!(e.iterations().windows(2).all(|w| w[0].depth < w[1].depth));
+    }
+
+    #[test]
+    fn progress() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let depths = Rc::new(RefCell::new(Vec::new()));
+        let mut e = Engine::new(1, 1 << 20);
+        let log = Rc::clone(&depths);
+        e.set_progress(move |m, info| log.borrow_mut().push((m.col(), info.depth)));
+        let m = e.iterative_depening(&Board::from_position("3332222"));
+
+        let depths = depths.borrow();
+        assert!(!depths.is_empty());
+        assert_eq!(*depths.last().unwrap(), (m.col(), m.depth()));
+        assert_eq!(depths.len(), e.iterations().len());
+    }
+
+    #[test]
+    fn stop() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+        use std::time::{Duration, Instant};
+
+        let mut e = Engine::new(60, 1 << 20);
+        e.set_infinite();
+        let handle = e.stop_handle();
+        let best = Rc::new(RefCell::new(Vec::new()));
+        let seen = Rc::clone(&best);
+        e.set_progress(move |m, info| {
+            seen.borrow_mut().push(m.col());
+            if info.depth >= 10 {
+                handle.stop();
+            }
+        });
+        let start = Instant::now();
+        let m = e.iterative_depening(&Board::from_position("33322224"));
+        assert!(start.elapsed() < Duration::from_secs(30));
+        assert!(m.depth() >= 10);
+        assert!(!e.info().timed_out);
+        assert!(e.stop_handle().is_stopped());
+        // the unlimited timer survives changes of the best move
+        assert!(best.borrow().windows(2).any(|w| w[0] != w[1]));
+    }
+
+    #[test]
+    fn movetime() {
+        use std::time::Instant;
+
+        let mut e = Engine::new(0, 1 << 20);
+        e.set_movetime(Duration::from_millis(250));
+        let start = Instant::now();
+        let m = e.iterative_depening(&Board::from_position("3332222"));
+        assert!(start.elapsed() < Duration::from_millis(400));
+        assert!(m.depth() > 0);
+        assert!(e.info().timed_out);
+    }
+
+    #[test]
+    fn clock() {
+        use std::time::Instant;
+
+        let mut e = Engine::new(0, 1 << 20);
+        e.set_clock(Clock {
+            remaining: Duration::from_secs(2),
+            increment: Duration::ZERO,
+            moves_to_go: None,
+        });
+        let board = Board::from_position("3332222");
+        let start = Instant::now();
+        let m = e.iterative_depening(&board);
+        // a share of the clock for one of the 18 moves left
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert!(m.depth() > 0);
+    }
+
+    #[test]
+    fn limits() {
+        let board = Board::from_position("3332222");
+        let run = |depth: Option<u8>, nodes: Option<u64>| {
+            let mut e = Engine::new(0, 1 << 20);
+            e.set_infinite();
+            e.set_threads(4);
+            e.set_max_depth(depth);
+            e.set_max_nodes(nodes);
+            let m = e.iterative_depening(&board);
+            (m.col(), m.score(), *m.pv(), m.depth(), e.info().nodes)
+        };
+
+        let a = run(Some(6), None);
+        assert_eq!(a.3, 6);
+        assert_eq!(a, run(Some(6), None));
+
+        let b = run(None, Some(20_000));
+        assert!(b.4 <= 20_000);
+        assert_eq!(b, run(None, Some(20_000)));
+        assert_eq!(run(Some(6), Some(1 << 40)), a);
+
+        // nor on the table size
+        let mut e = Engine::new(0, 64 << 20);
+        e.set_infinite();
+        e.set_max_nodes(Some(20_000));
+        let m = e.iterative_depening(&board);
+        assert_eq!((m.col(), m.score(), *m.pv(), m.depth(), e.info().nodes), b);
+
+        // the table of the previous searches is not used
+        let mut e = Engine::new(0, 1 << 20);
+        e.set_infinite();
+        let mut run = |depth: Option<u8>, nodes: Option<u64>| {
+            e.set_max_depth(depth);
+            e.set_max_nodes(nodes);
+            let m = e.iterative_depening(&board);
+            (m.col(), m.score(), *m.pv(), m.depth(), e.info().nodes)
+        };
+        assert_eq!(run(None, Some(20_000)), b);
+        assert_eq!(run(None, Some(20_000)), b);
+        assert_eq!(run(Some(6), None), a);
+        assert_eq!(run(Some(6), None), a);
+    }
+
+    #[test]
+    fn analyze() {
+        let board = Board::from_position("3332222");
+        let mut e = Engine::new(0, 1 << 20);
+        e.set_infinite();
+        e.set_max_depth(Some(6));
+        let moves = e.analyze(&board);
+        assert_eq!(moves.len(), 7);
+        // best first for P2 is the lowest score first
+        assert!(moves.windows(2).all(|w| w[0].score() <= w[1].score()));
+        assert!(moves.iter().all(|m| m.depth() == 6 || m.is_proven()));
+        assert_eq!(moves[0].col(), e.iterative_depening(&board).col());
+
+        // P1 wins at once with 2 or 5, one move later with the others
+        let board = Board::from_position("33440066006611");
+        let mut e = Engine::new(60, 1 << 20);
+        e.set_max_depth(Some(4));
+        let moves = e.analyze(&board);
+        assert_eq!(moves.len(), 7);
+        assert!(moves[..2]
+            .iter()
+            .all(|m| m.score() == W1 - 15 && m.is_proven()));
+        assert!(moves[..2].iter().any(|m| m.col() == 2));
+        assert!(e.analyze(&Board::from_position("0101010")).is_empty());
+    }
+
+    #[test]
+    fn ponder() {
+        use std::{thread, time};
+
+        // the time runs out before the first depth, only pondering helps
+        let board = Board::from_position("333222");
+        let hit = Board::from_position("3332222");
+        let mut e = Engine::new(0, 1 << 20);
+        assert_eq!(e.iterative_depening(&hit).depth(), 0);
+
+        e.ponder(&board, Some(2));
+        thread::sleep(time::Duration::from_millis(200));
+        let m = e.iterative_depening(&hit);
+        assert!(m.depth() > 0);
+        assert!(hit.legal_moves().contains(&m.col()));
+
+        // a miss only leaves the table filled
+        e.ponder(&board, Some(2));
+        thread::sleep(time::Duration::from_millis(200));
+        let m = e.iterative_depening(&Board::from_position("3332224"));
+        assert_eq!(m.depth(), 0);
+
+        // the best move of the pondered position changes between depths
+        let mut e = Engine::new(0, 1 << 20);
+        e.ponder(&Board::from_position("3332222"), Some(4));
+        thread::sleep(time::Duration::from_millis(1500));
+        let m = e.iterative_depening(&Board::from_position("33322224"));
+        assert!(m.depth() > 8);
+    }
+
+    #[test]
+    fn kept_table_pv() {
+        let board = Board::from_position("3332222");
+        let mut e = Engine::new(0, 1 << 20);
+        e.set_movetime(Duration::from_millis(200));
+        let first = e.iterative_depening(&board);
+        // the second search finds the position in the table
+        let second = e.iterative_depening(&board);
+        assert!(first.pv().moves().len() > 2);
+        assert!(second.pv().moves().len() > 2);
+    }
+
+    #[test]
+    fn skill() {
+        let board = Board::from_position("3332222");
+        let game = |level: u8, seed: u64| {
+            let mut e = Engine::new(60, 1 << 20);
+            e.set_skill(Skill::new(level, seed));
+            (0..5)
+                .map(|_| {
+                    let m = e.iterative_depening(&board);
+                    (m.col(), m.score(), m.depth())
+                })
+                .collect::<Vec<_>>()
+        };
+        let weak = game(0, 7);
+        assert_eq!(weak, game(0, 7));
+        assert!(weak.iter().all(|m| m.2 == 1));
+        // noise and second best moves vary from search to search
+        assert!(weak.iter().any(|m| m != &weak[0]));
+        assert!(game(4, 7).iter().all(|m| m.2 <= 9));
     }
 
     #[test]
@@ -268,11 +1296,12 @@ mod tests {
 
         let mut board = Board::new();
         board.make_move(3);
-        let mut e = Engine::new(3, 100_000);
+        let mut e = Engine::new(3, 1 << 20);
         e.get_ready();
+        let mut search = e.search(&Arc::new(AtomicBool::new(false)));
 
         let start = Instant::now();
-        _ = e.alpha_beta(&mut board, MIN, MAX, 12);
+        _ = search.alpha_beta(&mut board, MIN, MAX, 12, &mut Pv::new());
         let duration = start.elapsed();
         println!("Time elapsed in alpha_beta is: {:?}", duration);
     }
@@ -280,9 +1309,137 @@ mod tests {
     #[test]
     fn dtb() {
         let mut board = Board::new();
-        let mut e = Engine::new(3, 100_000);
+        let mut e = Engine::new(3, 1 << 20);
         e.get_ready();
-        let s: Score = e.alpha_beta(&mut board, MIN, MAX, GOOD_QUERY).unwrap();
-        println!("{}", ScoreMethods::to_string(&s))
+        let mut search = e.search(&Arc::new(AtomicBool::new(false)));
+        let mut pv = Pv::new();
+        let s: Score = search
+            .alpha_beta(&mut board, MIN, MAX, GOOD_QUERY, &mut pv)
+            .unwrap();
+        println!("{} {}", ScoreMethods::to_string(&s), pv)
+    }
+
+    #[test]
+    fn convention() {
+        let e = Engine::new(3, 1 << 20);
+        let mut search = e.search(&Arc::new(AtomicBool::new(false)));
+        let mut pv = Pv::new();
+
+        // P1 to move wins at once
+        let mut board = Board::from_position("33440066006611");
+        let s = search.alpha_beta(&mut board, MIN, MAX, 2, &mut pv).unwrap();
+        assert_eq!(s, W1 - 15);
+        // P2 to move can block only one of the two threats
+        let mut board = Board::from_position("3344006600661");
+        let s = search.alpha_beta(&mut board, MIN, MAX, 4, &mut pv).unwrap();
+        assert_eq!(s, W1 - 15);
+        assert_eq!(pv.moves().len(), 2);
+    }
+
+    #[test]
+    fn solve() {
+        let mut e = Engine::new(3, 1 << 20);
+
+        let board = Board::from_position("33440066006611");
+        let sol = e.solve(&board, None);
+        assert!(sol.proven);
+        assert_eq!(sol.score, W1 - 15);
+        assert_eq!(sol.distance, Some(1));
+        assert!(sol.pv.moves() == [2] || sol.pv.moves() == [5]);
+
+        let board = Board::from_position("3344006600661");
+        let sol = e.solve(&board, None);
+        assert!(sol.proven);
+        assert_eq!(sol.score, W1 - 15);
+        assert_eq!(sol.distance, Some(2));
+
+        // draw of an older database file
+        let board = Board::from_position("332233223322");
+        e.database = Arc::new(GameDatabase::with_positions(&[(
+            board.bitboard(),
+            DRAW + 112,
+        )]));
+        let sol = e.solve(&board, None);
+        assert!(sol.proven);
+        assert_eq!(sol.score, MINDRAW);
+        assert_eq!(sol.distance, Some(30));
+    }
+
+    #[test]
+    fn weak_solve() {
+        let mut e = Engine::new(3, 1 << 20);
+
+        let board = Board::from_position("33440066006611");
+        assert_eq!(e.weak_solve(&board, None), Some(Outcome::Win));
+        let board = Board::from_position("3344006600661");
+        assert_eq!(e.weak_solve(&board, None), Some(Outcome::Loss));
+
+        // draw of an older database file
+        let board = Board::from_position("332233223322");
+        e.database = Arc::new(GameDatabase::with_positions(&[(
+            board.bitboard(),
+            DRAW + 112,
+        )]));
+        assert_eq!(e.weak_solve(&board, None), Some(Outcome::Draw));
+    }
+
+    #[test]
+    fn forced() {
+        // would search for a minute if the moves were not forced
+        let mut e = Engine::new(60, 1 << 20);
+
+        let m = e.iterative_depening(&Board::from_position("33440066006611"));
+        assert_eq!(m.score(), W1 - 15);
+        let m = e.iterative_depening(&Board::from_position("3344006600661"));
+        assert_eq!(m.score(), W1 - 15);
+        assert_eq!(m.pv().moves().len(), 2);
+        let m = e.iterative_depening(&Board::from_position("001122"));
+        assert_eq!(m.col(), 3);
+    }
+
+    #[test]
+    fn threads() {
+        let board = Board::from_position("333333222222");
+        let mut e = Engine::new(1, 1 << 20);
+        e.set_threads(4);
+        let m = e.iterative_depening(&board);
+        assert!(m.depth() >= 3);
+        assert!(board.legal_moves().contains(&m.col()));
+    }
+
+    #[test]
+    fn ordering() {
+        let mut board = Board::new();
+        let e = Engine::new(1, 1 << 20);
+        let mut search = e.search(&Arc::new(AtomicBool::new(false)));
+        // no history, static evaluation order
+        let all = board.non_losing_moves();
+        assert_eq!(
+            *search.order_moves(&mut board, None, all),
+            [3, 4, 2, 5, 1, 6, 0]
+        );
+
+        search.store_cutoff(&board, 0, 4);
+        search.store_cutoff(&board, 6, 2);
+        board.make_move(1);
+        board.make_move(1);
+        // history first, then the centre
+        let all = board.non_losing_moves();
+        assert_eq!(
+            *search.order_moves(&mut board, None, all),
+            [0, 6, 3, 2, 4, 1, 5]
+        );
+        search.store_cutoff(&board, 5, 1);
+        assert_eq!(
+            *search.order_moves(&mut board, Some(2), all),
+            [2, 5, 0, 6, 3, 4, 1]
+        );
+
+        // without history the pruned twins stay out
+        let mut board = Board::from_position("33");
+        let e = Engine::new(1, 1 << 20);
+        let search = e.search(&Arc::new(AtomicBool::new(false)));
+        let pruned = board.bitboard().prune_symmetric(board.non_losing_moves());
+        assert_eq!(*search.order_moves(&mut board, None, pruned), [3, 2, 1, 0]);
     }
 }
@@ -0,0 +1,89 @@
+use std::fmt;
+
+use crate::board::{COL, ROW};
+
+const MAXLEN: usize = (COL * ROW) as usize;
+
+/// Principal variation, the sequence of columns from the searched position
+/// to the leaf that produced the score
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Pv {
+    moves: [u8; MAXLEN],
+    len: u8,
+}
+
+impl Default for Pv {
+    fn default() -> Self {
+        Pv::new()
+    }
+}
+
+impl Pv {
+    pub fn new() -> Pv {
+        Pv {
+            moves: [0; MAXLEN],
+            len: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// set the line to `col` followed by the line of the child position
+    pub fn update(&mut self, col: u8, child: &Pv) {
+        let n = (child.len as usize).min(MAXLEN - 1);
+        self.moves[0] = col;
+        self.moves[1..=n].copy_from_slice(&child.moves[..n]);
+        self.len = n as u8 + 1;
+    }
+
+    /// the same line played on the mirror image of the board
+    pub fn mirrored(&self) -> Pv {
+        let mut pv = *self;
+        for col in pv.moves[..pv.len as usize].iter_mut() {
+            *col = (COL as u8 - 1) - *col;
+        }
+        pv
+    }
+
+    pub fn moves(&self) -> &[u8] {
+        &self.moves[..self.len as usize]
+    }
+}
+
+impl fmt::Display for Pv {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, col) in self.moves().iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", col)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Pv {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}]", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update() {
+        let mut child = Pv::new();
+        child.update(4, &Pv::new());
+        let mut pv = Pv::new();
+        pv.update(3, &child);
+        assert_eq!(pv.moves(), [3, 4]);
+        assert_eq!(pv.to_string(), "3 4");
+        assert_eq!(pv.mirrored().moves(), [3, 2]);
+        pv.clear();
+        assert!(pv.moves().is_empty());
+    }
+}
@@ -17,29 +17,31 @@ const MINW1: Score = W1 - MAXMOVES;
 const MINW2: Score = W2 + MAXMOVES;
 
 pub const DRAW: Score = MINW2 + 1;
-const MINDRAW: Score = DRAW + MAXMOVES;
+pub const MINDRAW: Score = DRAW + MAXMOVES;
 
 pub trait ScoreMethods {
     fn to_string(&self) -> String;
     fn gamestate(&self) -> GameState;
+    /// stones on the board when the game ends, None if the score is not final
+    fn stones(&self) -> Option<u8>;
 }
 
 impl ScoreMethods for Score {
     fn to_string(&self) -> String {
+        match (self.gamestate(), self.stones()) {
+            (GameState::Draw, Some(d)) => format!("Draw with {} stones", d),
+            (GameState::WinP1, Some(d)) => format!("WinP1 with {} stones", d),
+            (GameState::WinP2, Some(d)) => format!("WinP2 with {} stones", d),
+            _ => std::string::ToString::to_string(&self),
+        }
+    }
+
+    fn stones(&self) -> Option<u8> {
         match self.gamestate() {
-            GameState::Open => std::string::ToString::to_string(&self),
-            GameState::Draw => {
-                let d = (self - W1).abs();
-                format!("Draw with {} stones", d)
-            }
-            GameState::WinP1 => {
-                let d = (self - W1).abs();
-                format!("WinP1 with {} stones", d)
-            }
-            GameState::WinP2 => {
-                let d = (self - W2).abs();
-                format!("WinP2 with {} stones", d)
-            }
+            GameState::Open => None,
+            GameState::Draw => Some((self - DRAW) as u8),
+            GameState::WinP1 => Some((W1 - self) as u8),
+            GameState::WinP2 => Some((self - W2) as u8),
         }
     }
 
@@ -58,9 +60,23 @@ impl ScoreMethods for Score {
 
 pub fn getscore(board: &Board) -> Score {
     match board.gamestate() {
-        GameState::Open => board.scoreboard().total_score(),
+        GameState::Open => board.scoreboard().map_or(EQUAL, |s| s.total_score()),
         GameState::Draw => DRAW + board.nmoves() as i32,
         GameState::WinP1 => W1 - board.nmoves() as i32,
         GameState::WinP2 => W2 + board.nmoves() as i32,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stones() {
+        assert_eq!((W1 - 15).stones(), Some(15));
+        assert_eq!((W2 + 16).stones(), Some(16));
+        assert_eq!((DRAW + 42).stones(), Some(42));
+        assert_eq!(EQUAL.stones(), None);
+        assert_eq!(ScoreMethods::to_string(&(DRAW + 42)), "Draw with 42 stones");
+    }
+}
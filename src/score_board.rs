@@ -52,13 +52,91 @@ impl ScoreSet {
     }
 }
 
-const NSC: usize = 207; // chissà se è giosto
+/// a cell is in at most 4 lines for each of the 4 directions
+const MAXLINES: usize = 16;
+
+/// Every line of four cells, numbered, and for every cell the lines it is in
+struct LineTable {
+    lines: [[[u8; MAXLINES]; COL as usize]; ROW as usize],
+    len: [[u8; COL as usize]; ROW as usize],
+    count: usize,
+}
+
+impl LineTable {
+    const fn add(&mut self, row: u64, col: u64) {
+        let (r, c) = (row as usize, col as usize);
+        self.lines[r][c][self.len[r][c] as usize] = self.count as u8;
+        self.len[r][c] += 1;
+    }
+
+    const fn new() -> LineTable {
+        let mut t = LineTable {
+            lines: [[[0; MAXLINES]; COL as usize]; ROW as usize],
+            len: [[0; COL as usize]; ROW as usize],
+            count: 0,
+        };
+        let mut i = 0;
+        while i < ROW {
+            let mut j = 0;
+            while j < COL {
+                let mut k = 0;
+                if ROW - i >= CONNECT {
+                    // orizontal --
+                    while k < CONNECT {
+                        t.add(i + k, j);
+                        k += 1;
+                    }
+                    t.count += 1;
+                }
+
+                if COL - j >= CONNECT {
+                    // vertical |
+                    k = 0;
+                    while k < CONNECT {
+                        t.add(i, j + k);
+                        k += 1;
+                    }
+                    t.count += 1;
+                }
+
+                if (ROW - i >= CONNECT) && (COL - j >= CONNECT) {
+                    // diagonal \
+                    k = 0;
+                    while k < CONNECT {
+                        t.add(i + k, j + k);
+                        k += 1;
+                    }
+                    t.count += 1;
+                }
+
+                if (ROW - i >= CONNECT) && (j + 1 >= CONNECT) {
+                    // diagonal /
+                    k = 0;
+                    while k < CONNECT {
+                        t.add(i + k, j - k);
+                        k += 1;
+                    }
+                    t.count += 1;
+                }
+                j += 1;
+            }
+            i += 1;
+        }
+        t
+    }
+
+    fn cell(&self, row: usize, col: usize) -> &[u8] {
+        &self.lines[row][col][..self.len[row][col] as usize]
+    }
+}
+
+static LINES: LineTable = LineTable::new();
+const NLINES: usize = LineTable::new().count;
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, Copy, PartialEq)]
 pub struct ScoreBoard {
     total_score: i32,
-    scoreboard: [[Vec<u32>; COL as usize]; ROW as usize],
-    scoresets: [ScoreSet; NSC],
+    scoresets: [ScoreSet; NLINES],
 }
 
 impl ScoreBoard {
@@ -70,7 +148,7 @@ impl ScoreBoard {
         let mut delta_score: i32 = 0;
         let mut win: bool = false;
 
-        for i in self.scoreboard[row][col].iter() {
+        for i in LINES.cell(row, col) {
             let sc = &mut self.scoresets[*i as usize];
             let ps = sc.score;
             sc.add(player);
@@ -92,7 +170,7 @@ impl ScoreBoard {
     pub fn unmake_move(&mut self, row: usize, col: usize, player: &Player) {
         let mut delta_score: i32 = 0;
 
-        for i in self.scoreboard[row][col].iter() {
+        for i in LINES.cell(row, col) {
             let sc = &mut self.scoresets[*i as usize];
             let ps = sc.score;
             sc.sub(player);
@@ -107,110 +185,9 @@ impl ScoreBoard {
     }
 
     pub fn new() -> ScoreBoard {
-        let sca: [ScoreSet; NSC] = [ScoreSet::init(); NSC];
-
-        let mut sbt: [[Vec<u32>; COL as usize]; ROW as usize] = [
-            [
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-            ],
-            [
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-            ],
-            [
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-            ],
-            [
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-            ],
-            [
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-            ],
-            [
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-                Vec::with_capacity(16),
-            ],
-        ];
-        let mut n: u32 = 0;
-        for i in 0..ROW {
-            for j in 0..COL {
-                if ROW - i >= CONNECT {
-                    // orizontal --
-                    for k in i..(i + CONNECT) {
-                        sbt[k as usize][j as usize].push(n);
-                    }
-                    n += 1;
-                }
-
-                if COL - j >= CONNECT {
-                    // vertical |
-                    for k in j..(j + CONNECT) {
-                        sbt[i as usize][k as usize].push(n);
-                    }
-                    n += 1;
-                }
-
-                if (ROW - i >= CONNECT) && (COL - j >= CONNECT) {
-                    // diagonal \
-                    let mut kk = j;
-                    for k in i..(i + CONNECT) {
-                        sbt[k as usize][kk as usize].push(n);
-                        kk += 1;
-                    }
-                    n += 1;
-                }
-
-                if (ROW - i >= CONNECT) && (j + 1 >= CONNECT) {
-                    // diagonal /
-                    let mut kk = j;
-                    for k in i..(i + CONNECT) {
-                        sbt[k as usize][kk as usize].push(n);
-                        kk = kk.saturating_sub(1);
-                    }
-                    n += 1;
-                }
-            }
-        }
-
-        let sb = sbt;
         ScoreBoard {
             total_score: 0,
-            scoreboard: sb,
-            scoresets: sca,
+            scoresets: [ScoreSet::init(); NLINES],
         }
     }
 }
@@ -255,4 +232,14 @@ mod tests {
         ss.make_move(2, 0, &crate::board::Player::P2);
         assert!(ss.make_move(3, 0, &crate::board::Player::P2));
     }
+
+    #[test]
+    fn lines() {
+        assert_eq!(super::NLINES, 69);
+        // a corner is in one line per direction, the centre of the bottom
+        // row in 4 horizontal, 1 vertical and 1 of each diagonal
+        assert_eq!(super::LINES.cell(0, 0).len(), 3);
+        assert_eq!(super::LINES.cell(0, 3).len(), 7);
+        assert_eq!(super::LINES.cell(2, 3).len(), 13);
+    }
 }
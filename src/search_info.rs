@@ -0,0 +1,101 @@
+use std::fmt;
+use std::time::Duration;
+
+/// Statistics of a search, of the whole search or up to a completed depth
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SearchInfo {
+    pub nodes: u64,
+    pub tt_probes: u64,
+    pub tt_hits: u64,
+    pub db_hits: u64,
+    pub cutoffs: u64,
+    /// last completed depth
+    pub depth: u8,
+    pub elapsed: Duration,
+    /// the timer ran out before the search finished
+    pub timed_out: bool,
+}
+
+impl SearchInfo {
+    /// nodes per second
+    pub fn nps(&self) -> u64 {
+        match self.elapsed.as_micros() {
+            0 => 0,
+            us => (self.nodes as u128 * 1_000_000 / us) as u64,
+        }
+    }
+
+    /// fraction of the table probes that found the position
+    pub fn tt_hit_rate(&self) -> f64 {
+        match self.tt_probes {
+            0 => 0.0,
+            probes => self.tt_hits as f64 / probes as f64,
+        }
+    }
+
+    /// add the counters of another thread of the same search
+    pub fn merge(&mut self, other: &SearchInfo) {
+        self.nodes += other.nodes;
+        self.tt_probes += other.tt_probes;
+        self.tt_hits += other.tt_hits;
+        self.db_hits += other.db_hits;
+        self.cutoffs += other.cutoffs;
+        self.depth = self.depth.max(other.depth);
+        self.elapsed = self.elapsed.max(other.elapsed);
+        self.timed_out |= other.timed_out;
+    }
+}
+
+impl fmt::Display for SearchInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "depth {} nodes {} nps {} tt {}/{} ({:.1}%) db {} cutoffs {} time {}ms",
+            self.depth,
+            self.nodes,
+            self.nps(),
+            self.tt_hits,
+            self.tt_probes,
+            self.tt_hit_rate() * 100.0,
+            self.db_hits,
+            self.cutoffs,
+            self.elapsed.as_millis()
+        )?;
+        if self.timed_out {
+            write!(f, " (timed out)")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge() {
+        let mut a = SearchInfo {
+            nodes: 1000,
+            tt_probes: 10,
+            tt_hits: 5,
+            depth: 7,
+            elapsed: Duration::from_millis(500),
+            ..Default::default()
+        };
+        let b = SearchInfo {
+            nodes: 1000,
+            tt_probes: 10,
+            depth: 8,
+            elapsed: Duration::from_millis(400),
+            timed_out: true,
+            ..Default::default()
+        };
+        a.merge(&b);
+        assert_eq!(a.nodes, 2000);
+        assert_eq!(a.nps(), 4000);
+        assert_eq!(a.tt_hit_rate(), 0.25);
+        assert_eq!(a.depth, 8);
+        assert!(a.timed_out);
+        assert_eq!(SearchInfo::default().nps(), 0);
+    }
+}
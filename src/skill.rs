@@ -0,0 +1,97 @@
+use crate::score::*;
+
+/// full strength, no handicap
+pub const MAX_SKILL: u8 = 10;
+
+/// Handicaps of a playing strength, all of them shrink as the level grows.
+/// Below `MAX_SKILL` the search is depth limited, root scores get random
+/// noise and sometimes the second best move is played; the random choices
+/// depend only on the seed, so the games can be replayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Skill {
+    level: u8,
+    rng: u64,
+}
+
+impl Skill {
+    pub fn new(level: u8, seed: u64) -> Skill {
+        Skill {
+            level: level.min(MAX_SKILL),
+            rng: seed,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn level(&self) -> u8 {
+        self.level
+    }
+
+    pub fn is_handicapped(&self) -> bool {
+        self.level < MAX_SKILL
+    }
+
+    /// splitmix64
+    fn next(&mut self) -> u64 {
+        self.rng = self.rng.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    fn handicap(&self) -> u8 {
+        MAX_SKILL - self.level
+    }
+
+    /// deepest search, from 1 ply at level 0
+    pub fn max_depth(&self) -> Option<u8> {
+        match self.is_handicapped() {
+            true => Some(1 + 2 * self.level),
+            false => None,
+        }
+    }
+
+    /// random amount to add to a root score
+    pub fn noise(&mut self) -> Score {
+        let amplitude = 15 * self.handicap() as u64;
+        if amplitude == 0 {
+            return EQUAL;
+        }
+        (self.next() % (2 * amplitude + 1)) as Score - amplitude as Score
+    }
+
+    /// true when the second best move should be played, up to 40% of the
+    /// moves at level 0
+    pub fn second_best(&mut self) -> bool {
+        (self.next() % 100) < 4 * self.handicap() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handicap() {
+        let mut full = Skill::new(MAX_SKILL + 5, 1);
+        assert_eq!(full.level(), MAX_SKILL);
+        assert_eq!(full.max_depth(), None);
+        assert_eq!(full.noise(), EQUAL);
+        assert!(!full.second_best());
+
+        let mut weak = Skill::new(0, 1);
+        assert_eq!(weak.max_depth(), Some(1));
+        let noise: Vec<Score> = (0..100).map(|_| weak.noise()).collect();
+        assert!(noise.iter().all(|n| n.abs() <= 150));
+        assert!(noise.iter().any(|&n| n != EQUAL));
+
+        // same seed, same choices
+        let mut a = Skill::new(3, 42);
+        let mut b = Skill::new(3, 42);
+        for _ in 0..10 {
+            assert_eq!(a.noise(), b.noise());
+            assert_eq!(a.second_best(), b.second_best());
+        }
+        assert!(Skill::new(3, 0).max_depth() < Skill::new(4, 0).max_depth());
+    }
+}
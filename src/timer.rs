@@ -5,12 +5,44 @@ use std::time::{Duration, Instant};
 
 use std::fmt;
 
+/// Search time limits: past the soft limit no new depth is started, at the
+/// hard limit the search is aborted
 #[derive(Debug, Clone)]
 pub struct Timer {
-    duration: Duration,
+    soft: Duration,
+    hard: Duration,
     start: Instant,
 }
 
+/// time kept for the program to answer after the search
+const MOVE_OVERHEAD: Duration = Duration::from_millis(20);
+
+/// Clock of the side to move in a timed game
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Clock {
+    pub remaining: Duration,
+    /// time added after every move
+    pub increment: Duration,
+    /// moves until the next time control, the rest of the game if None
+    pub moves_to_go: Option<u32>,
+}
+
+impl Clock {
+    /// limits for the next move with `free_cells` empty cells on the board:
+    /// the time left is shared among our moves still to play, a search may
+    /// go up to three times its share when the best move is unstable
+    pub fn timer(&self, free_cells: u8) -> Timer {
+        let usable = self.remaining.saturating_sub(MOVE_OVERHEAD);
+        let moves = self
+            .moves_to_go
+            .unwrap_or((free_cells as u32).div_ceil(2))
+            .max(1);
+        let target = usable / moves + self.increment * 3 / 4;
+        let hard = (target * 3).min(usable);
+        Timer::with_limits(target / 2, hard)
+    }
+}
+
 #[derive(Debug)]
 pub struct TimeoutError;
 
@@ -22,27 +54,63 @@ impl fmt::Display for TimeoutError {
 
 impl Timer {
     pub fn new(seconds: u64) -> Timer {
+        Timer::movetime(Duration::from_secs(seconds))
+    }
+
+    /// `budget` for the whole search, a new depth is not started after half
+    /// of it because it would hardly finish in time
+    pub fn movetime(budget: Duration) -> Timer {
+        Timer::with_limits(budget / 2, budget)
+    }
+
+    pub fn with_limits(soft: Duration, hard: Duration) -> Timer {
         Timer {
-            duration: Duration::new(seconds, 0),
+            soft: soft.min(hard),
+            hard,
             start: Instant::now(),
         }
     }
 
+    /// timer that never runs out
+    pub fn unlimited() -> Timer {
+        Timer::with_limits(Duration::MAX, Duration::MAX)
+    }
+
     pub fn start(&mut self) {
         self.start = Instant::now();
     }
 
+    /// time since the start
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// error once the hard limit is reached
     pub fn check(&self) -> Result<Duration, TimeoutError> {
         let elapsed = self.start.elapsed();
-        if elapsed >= self.duration {
+        if elapsed >= self.hard {
             Err(TimeoutError)
         } else {
             Ok(elapsed)
         }
     }
 
+    /// true once the soft limit is reached
+    pub fn soft_expired(&self) -> bool {
+        self.start.elapsed() >= self.soft
+    }
+
+    /// spend more time before the next depth, up to the hard limit
+    pub fn extend_soft(&mut self) {
+        self.soft = self.soft.saturating_add(self.soft / 2).min(self.hard);
+    }
+
     pub fn set_duration(&mut self, seconds: u64) {
-        self.duration = Duration::new(seconds, 0);
+        self.set_movetime(Duration::from_secs(seconds));
+    }
+
+    pub fn set_movetime(&mut self, budget: Duration) {
+        *self = Timer::movetime(budget);
     }
 }
 
@@ -60,4 +128,50 @@ mod tests {
         thread::sleep(second);
         t.check().unwrap();
     }
+
+    #[test]
+    fn limits() {
+        let t = Timer::with_limits(Duration::from_millis(20), Duration::from_millis(60));
+        assert!(!t.soft_expired());
+        thread::sleep(Duration::from_millis(30));
+        assert!(t.soft_expired());
+        assert!(t.check().is_ok());
+        thread::sleep(Duration::from_millis(40));
+        assert!(t.check().is_err());
+
+        let t = Timer::movetime(Duration::from_millis(250));
+        assert_eq!(t.soft, Duration::from_millis(125));
+        assert_eq!(t.hard, Duration::from_millis(250));
+    }
+
+    #[test]
+    fn clock() {
+        let clock = Clock {
+            remaining: Duration::from_millis(20_020),
+            increment: Duration::ZERO,
+            moves_to_go: None,
+        };
+        // 20 moves left for each player
+        let t = clock.timer(40);
+        assert_eq!(t.soft, Duration::from_millis(500));
+        assert_eq!(t.hard, Duration::from_millis(3000));
+        // later moves get a bigger share
+        assert!(clock.timer(10).soft > t.soft);
+
+        let clock = Clock {
+            moves_to_go: Some(1),
+            ..clock
+        };
+        assert_eq!(clock.timer(40).hard, Duration::from_secs(20));
+
+        let mut t = Timer::with_limits(Duration::from_millis(100), Duration::from_millis(200));
+        t.extend_soft();
+        assert_eq!(t.soft, Duration::from_millis(150));
+        t.extend_soft();
+        assert_eq!(t.soft, Duration::from_millis(200));
+
+        let mut t = Timer::unlimited();
+        t.extend_soft();
+        assert_eq!(t.soft, Duration::MAX);
+    }
 }
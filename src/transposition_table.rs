@@ -1,73 +1,234 @@
 use crate::bit_board::*;
+use crate::board::COL;
 use crate::score::*;
-use std::collections::hash_map::DefaultHasher;
-use std::hash::Hasher;
-use std::hash::*;
+use std::mem::size_of;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
 
-#[derive(Clone, Default)]
+/// How the stored score relates to the real score of the position
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Bound {
+    #[default]
+    Exact,
+    /// real score >= stored score (the search failed high)
+    Lower,
+    /// real score <= stored score (the search failed low)
+    Upper,
+}
+
+#[derive(Clone, Copy, Default)]
 pub struct Entry {
     score: Score,
-    key: BitBoard,
+    depth: u8,
+    bound: Bound,
+    best: Option<u8>,
+    generation: u8,
+}
+
+const NO_MOVE: u64 = 0b1111;
+
+impl Entry {
+    pub fn best(&self) -> Option<u8> {
+        self.best
+    }
+
+    /// score usable by a search of `depth` plies in the (`alpha`, `beta`)
+    /// window, None if the entry is too shallow or its bound is not enough
+    pub fn cutoff(&self, alpha: Score, beta: Score, depth: u8) -> Option<Score> {
+        if self.depth < depth {
+            return None;
+        }
+        match self.bound {
+            Bound::Exact => Some(self.score),
+            Bound::Lower if self.score >= beta => Some(self.score),
+            Bound::Upper if self.score <= alpha => Some(self.score),
+            _ => None,
+        }
+    }
+
+    fn mirrored(mut self) -> Entry {
+        self.best = self.best.map(|col| (COL as u8 - 1) - col);
+        self
+    }
+
+    /// | generation 8 | best 4 | bound 2 | depth 8 | score 32 |
+    fn pack(&self) -> u64 {
+        let bound: u64 = match self.bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2,
+        };
+        let best = self.best.map_or(NO_MOVE, |col| col as u64);
+        (self.score as u32 as u64)
+            | (self.depth as u64) << 32
+            | bound << 40
+            | best << 42
+            | (self.generation as u64) << 46
+    }
+
+    fn unpack(data: u64) -> Entry {
+        let bound = match (data >> 40) & 0b11 {
+            0 => Bound::Exact,
+            1 => Bound::Lower,
+            _ => Bound::Upper,
+        };
+        let best = match (data >> 42) & 0b1111 {
+            NO_MOVE => None,
+            col => Some(col as u8),
+        };
+        Entry {
+            score: data as u32 as Score,
+            depth: (data >> 32) as u8,
+            bound,
+            best,
+            generation: (data >> 46) as u8,
+        }
+    }
+}
+
+/// Lockless slot, the key is stored xored with the data so an entry torn
+/// by concurrent writes does not verify and is ignored
+#[derive(Default)]
+struct Slot {
+    check: AtomicU64,
+    data: AtomicU64,
+}
+
+impl Slot {
+    fn load(&self) -> (u64, Entry) {
+        let data = self.data.load(Ordering::Relaxed);
+        let check = self.check.load(Ordering::Relaxed);
+        (check ^ data, Entry::unpack(data))
+    }
+
+    fn get(&self, key: u64) -> Option<Entry> {
+        match self.load() {
+            (k, entry) if k == key => Some(entry),
+            _ => None,
+        }
+    }
+
+    fn store(&self, key: u64, entry: &Entry) {
+        let data = entry.pack();
+        self.check.store(key ^ data, Ordering::Relaxed);
+        self.data.store(data, Ordering::Relaxed);
+    }
+}
+
+/// Two-tier bucket: `deep` keeps the deepest entry of the current search,
+/// `recent` is always replaced
+#[derive(Default)]
+struct Bucket {
+    deep: Slot,
+    recent: Slot,
 }
 
+impl Bucket {
+    fn get(&self, key: u64) -> Option<Entry> {
+        self.deep.get(key).or_else(|| self.recent.get(key))
+    }
+}
+
+/// Transposition table kept across iterations and moves, entries of older
+/// searches are replaced first. It can be shared between search threads.
 pub struct Table {
-    size: usize,
-    table: Box<[Entry]>,
+    /// memory budget in bytes
+    memory: usize,
+    table: Box<[Bucket]>,
+    generation: AtomicU8,
 }
 
 impl Table {
-    pub fn new(size: usize) -> Table {
+    pub fn new(memory: usize) -> Table {
         Table {
-            size,
-            table: vec![Default::default(); 1].into_boxed_slice(),
+            memory,
+            table: Self::buckets_array(1),
+            generation: AtomicU8::new(0),
         }
     }
 
-    pub fn alloc(&mut self) {
-        self.table = vec![Default::default(); self.size].into_boxed_slice();
+    fn buckets_array(n: usize) -> Box<[Bucket]> {
+        (0..n).map(|_| Default::default()).collect()
     }
 
-    pub fn clean(&mut self) {
-        self.alloc();
+    /// largest power of two number of buckets that fits in the budget
+    fn buckets(&self) -> usize {
+        let n = (self.memory / size_of::<Bucket>()).max(1);
+        1 << n.ilog2()
+    }
+
+    /// number of entries the memory budget can hold
+    pub fn entries(&self) -> usize {
+        self.buckets() * 2
+    }
+
+    fn alloc(&mut self) {
+        self.table = Self::buckets_array(self.buckets());
     }
 
+    /// allocate the table only if the size changed
     pub fn get_ready(&mut self) {
+        if self.table.len() != self.buckets() {
+            self.alloc();
+        }
+    }
+
+    /// forget every entry, as if the table was just built
+    pub fn clear(&mut self) {
         self.alloc();
+        self.generation = AtomicU8::new(0);
     }
 
-    fn get_index(&self, key: &BitBoard) -> usize {
-        let mut s = DefaultHasher::new();
-        key.hash(&mut s);
-        let n: usize = s.finish() as usize;
-        n % self.size
+    /// start a new search, entries of previous ones become replaceable
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
     }
 
-    pub fn get(&self, key: &DoubleBitBoard) -> Option<Score> {
-        let index1 = self.get_index(&key.board());
-        if self.table[index1].key == key.board() {
-            Some(self.table[index1].score)
-        } else {
-            // check if is stored as a mirrored position
-            let index2 = self.get_index(&key.board_mirrored());
-            if self.table[index2].key == key.board_mirrored() {
-                Some(self.table[index2].score)
-            } else {
-                None
+    fn get_index(&self, hash: u64) -> usize {
+        hash as usize & (self.table.len() - 1)
+    }
+
+    pub fn get(&self, key: &DoubleBitBoard) -> Option<Entry> {
+        let index1 = self.get_index(key.hash());
+        match self.table[index1].get(key.board().table_key()) {
+            Some(entry) => Some(entry),
+            None => {
+                // check if is stored as a mirrored position
+                let index2 = self.get_index(key.hash_mirrored());
+                self.table[index2]
+                    .get(key.board_mirrored().table_key())
+                    .map(|entry| entry.mirrored())
             }
         }
     }
 
-    pub fn set(&mut self, key: DoubleBitBoard, score: Score) {
-        let index = self.get_index(&key.board());
+    pub fn set(
+        &self,
+        key: DoubleBitBoard,
+        score: Score,
+        depth: u8,
+        bound: Bound,
+        best: Option<u8>,
+    ) {
+        let generation = self.generation.load(Ordering::Relaxed);
         let entry: Entry = Entry {
             score,
-            key: key.board(),
+            depth,
+            bound,
+            best,
+            generation,
         };
-        self.table[index] = entry;
+        let bucket = &self.table[self.get_index(key.hash())];
+        let key = key.board().table_key();
+        let (deep_key, deep) = bucket.deep.load();
+        if deep_key == key || deep.generation != generation || deep.depth <= depth {
+            bucket.deep.store(key, &entry);
+        } else {
+            bucket.recent.store(key, &entry);
+        }
     }
 
-    pub fn set_size(&mut self, size: usize) {
-        self.size = size;
+    pub fn set_size(&mut self, memory: usize) {
+        self.memory = memory;
     }
 }
 
@@ -81,8 +242,101 @@ mod tests {
         table.get_ready();
         let mut bitboard = DoubleBitBoard::new();
         bitboard.make_move(0, &crate::board::Player::P1);
-        table.set(bitboard, EQUAL);
-        let sc = table.get(&bitboard).unwrap();
-        assert_eq!(sc, EQUAL);
+        table.set(bitboard, EQUAL, 4, Bound::Exact, Some(1));
+        let entry = table.get(&bitboard).unwrap();
+        assert_eq!(entry.cutoff(MIN, MAX, 4), Some(EQUAL));
+        assert_eq!(entry.cutoff(MIN, MAX, 5), None);
+        assert_eq!(entry.best(), Some(1));
+    }
+
+    #[test]
+    fn empty() {
+        let mut table = Table::new(100);
+        table.get_ready();
+        // the empty board does not match the zeroed slots
+        assert!(table.get(&DoubleBitBoard::new()).is_none());
+    }
+
+    #[test]
+    fn bounds() {
+        let mut table = Table::new(100);
+        table.get_ready();
+        let mut bitboard = DoubleBitBoard::new();
+        bitboard.make_move(0, &crate::board::Player::P1);
+        table.set(bitboard, 50, 4, Bound::Lower, Some(0));
+        let entry = table.get(&bitboard).unwrap();
+        assert_eq!(entry.cutoff(0, 40, 4), Some(50));
+        assert_eq!(entry.cutoff(0, 60, 4), None);
+
+        table.set(bitboard, -50, 4, Bound::Upper, None);
+        let entry = table.get(&bitboard).unwrap();
+        assert_eq!(entry.cutoff(-40, 0, 4), Some(-50));
+        assert_eq!(entry.cutoff(-60, 0, 4), None);
+    }
+
+    #[test]
+    fn mirrored() {
+        let mut table = Table::new(100);
+        table.get_ready();
+        let mut bitboard = DoubleBitBoard::new();
+        bitboard.make_move(0, &crate::board::Player::P1);
+        table.set(bitboard, EQUAL, 4, Bound::Exact, Some(1));
+
+        let mut mirrored = DoubleBitBoard::new();
+        mirrored.make_move(6, &crate::board::Player::P1);
+        assert_eq!(table.get(&mirrored).unwrap().best(), Some(5));
+    }
+
+    #[test]
+    fn pack() {
+        let entry = Entry {
+            score: W2 + 12,
+            depth: 41,
+            bound: Bound::Upper,
+            best: Some(6),
+            generation: 255,
+        };
+        let unpacked = Entry::unpack(entry.pack());
+        assert_eq!(unpacked.score, entry.score);
+        assert_eq!(unpacked.depth, entry.depth);
+        assert_eq!(unpacked.bound, entry.bound);
+        assert_eq!(unpacked.best, entry.best);
+        assert_eq!(unpacked.generation, entry.generation);
+        assert_eq!(Entry::unpack(Entry::default().pack()).best, None);
+    }
+
+    #[test]
+    fn memory() {
+        let mut table = Table::new(1 << 20);
+        table.get_ready();
+        assert!(table.entries().is_power_of_two());
+        assert!(table.table.len() * size_of::<Bucket>() <= 1 << 20);
+        assert!(table.table.len() * size_of::<Bucket>() * 2 > 1 << 20);
+    }
+
+    #[test]
+    fn replacement() {
+        // a single bucket, every position collides
+        let mut table = Table::new(size_of::<Bucket>());
+        table.get_ready();
+        let mut deep = DoubleBitBoard::new();
+        deep.make_move(0, &crate::board::Player::P1);
+        let mut shallow = DoubleBitBoard::new();
+        shallow.make_move(1, &crate::board::Player::P1);
+        let mut other = DoubleBitBoard::new();
+        other.make_move(2, &crate::board::Player::P1);
+
+        table.set(deep, EQUAL, 8, Bound::Exact, None);
+        table.set(shallow, EQUAL, 2, Bound::Exact, None);
+        table.set(other, EQUAL, 3, Bound::Exact, None);
+        assert!(table.get(&deep).is_some());
+        assert!(table.get(&shallow).is_none());
+        assert!(table.get(&other).is_some());
+
+        // entries of an old search are replaced even if deeper
+        table.new_search();
+        table.set(shallow, EQUAL, 2, Bound::Exact, None);
+        assert!(table.get(&deep).is_none());
+        assert!(table.get(&shallow).is_some());
     }
 }